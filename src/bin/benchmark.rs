@@ -1,13 +1,21 @@
+use std::collections::{BTreeMap, VecDeque};
 use std::io::{self, Read, Write};
 use std::time::{Duration, Instant};
 
 use clap::{Parser, Subcommand};
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
 use rusty_foam::byte_stream::ByteStream;
+use rusty_foam::error::FoamError;
 use serde::{Deserialize, Serialize};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
+    /// Print results as JSON
+    #[arg(long, global = true)]
+    json: bool,
+
     #[command(subcommand)]
     command: Command,
 }
@@ -21,6 +29,49 @@ enum Command {
         #[arg(long, default_value_t = 4096)]
         capacity: usize,
 
+        /// Comma-separated capacities to benchmark in one run (e.g. 1K,4K,16M), overrides
+        /// --capacity
+        #[arg(long, value_delimiter = ',', value_parser = parse_size)]
+        capacity_sweep: Option<Vec<usize>>,
+
+        /// Number of read/write cycles
+        #[arg(long, default_value_t = 1024)]
+        cycles: usize,
+
+        /// Write size
+        #[arg(long, default_value_t = 1024)]
+        write_size: usize,
+
+        /// Number of writes per cycle
+        #[arg(long, default_value_t = 1)]
+        writes_per_cycle: usize,
+
+        /// Read size
+        #[arg(long, default_value_t = 1024)]
+        read_size: usize,
+
+        /// Number of reads per cycle
+        #[arg(long, default_value_t = 1)]
+        reads_per_cycle: usize,
+
+        /// Distribution of write sizes (`fixed:N` or `uniform:MIN:MAX`), overrides --write-size
+        #[arg(long, value_parser = parse_size_dist)]
+        write_size_dist: Option<SizeDist>,
+
+        /// Distribution of read sizes (`fixed:N` or `uniform:MIN:MAX`), overrides --read-size
+        #[arg(long, value_parser = parse_size_dist)]
+        read_size_dist: Option<SizeDist>,
+
+        /// Seed for drawing operation sizes from the distributions
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+    /// Run the same workload against byte stream and standard library buffers
+    Compare {
+        /// Capacity of byte stream, also used as the initial capacity of the other buffers
+        #[arg(long, default_value_t = 4096)]
+        capacity: usize,
+
         /// Number of read/write cycles
         #[arg(long, default_value_t = 1024)]
         cycles: usize,
@@ -43,22 +94,191 @@ enum Command {
     },
 }
 
-fn benchmark_byte_stream(
-    mut byte_stream: ByteStream,
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum SizeDist {
+    Fixed(usize),
+    Uniform(usize, usize),
+}
+
+impl SizeDist {
+    fn sample(&self, rng: &mut StdRng) -> usize {
+        match *self {
+            SizeDist::Fixed(size) => size,
+            SizeDist::Uniform(min, max) => rng.random_range(min..=max),
+        }
+    }
+}
+
+/// Number of operations per power-of-two size bucket, keyed by the bucket's upper bound.
+#[derive(Default, Serialize)]
+struct SizeHistogram {
+    writes: BTreeMap<usize, usize>,
+    reads: BTreeMap<usize, usize>,
+}
+
+impl SizeHistogram {
+    fn bucket(size: usize) -> usize {
+        if size == 0 {
+            0
+        } else {
+            size.next_power_of_two()
+        }
+    }
+
+    fn record_write(&mut self, size: usize) {
+        *self.writes.entry(Self::bucket(size)).or_default() += 1;
+    }
+
+    fn record_read(&mut self, size: usize) {
+        *self.reads.entry(Self::bucket(size)).or_default() += 1;
+    }
+}
+
+/// `Vec<u8>` buffer that shifts the remaining data to the front on every read.
+struct ShiftingBuffer(Vec<u8>);
+
+impl Read for ShiftingBuffer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = buf.len().min(self.0.len());
+        buf[..size].copy_from_slice(&self.0[..size]);
+        self.0.drain(..size);
+        Ok(size)
+    }
+}
+
+impl Write for ShiftingBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct BenchmarkResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backend: Option<&'static str>,
+    capacity: usize,
+    bytes: usize,
+    elapsed: Duration,
+    throughput_mib_s: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size_histogram: Option<SizeHistogram>,
+}
+
+impl BenchmarkResult {
+    fn new(capacity: usize, bytes: usize, duration: Duration) -> Self {
+        Self {
+            backend: None,
+            capacity,
+            bytes,
+            elapsed: duration,
+            throughput_mib_s: bytes as f64 / duration.as_secs_f64() / (1024.0 * 1024.0),
+            size_histogram: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    command: &'a Command,
+    results: Vec<BenchmarkResult>,
+}
+
+fn parse_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    let value: usize = digits.parse().map_err(|_| format!("invalid size `{s}`"))?;
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size `{s}` is too large"))
+}
+
+fn parse_size_dist(s: &str) -> Result<SizeDist, String> {
+    let parts: Vec<&str> = s.split(':').collect();
+    match parts.as_slice() {
+        ["fixed", size] => Ok(SizeDist::Fixed(parse_size(size)?)),
+        ["uniform", min, max] => {
+            let (min, max) = (parse_size(min)?, parse_size(max)?);
+            if min > max {
+                return Err(format!("minimum is larger than maximum in `{s}`"));
+            }
+            Ok(SizeDist::Uniform(min, max))
+        }
+        _ => Err(format!(
+            "invalid distribution `{s}`, expected `fixed:N` or `uniform:MIN:MAX`"
+        )),
+    }
+}
+
+fn make_corpus(len: usize) -> Vec<u8> {
+    (b'A'..=b'Z')
+        .chain(b'a'..=b'z')
+        .chain(b'0'..=b'9')
+        .cycle()
+        .take(len)
+        .collect()
+}
+
+/// Checks that a fixed-size workload never overfills a stream of `capacity` bytes or reads more
+/// than has been written, which would otherwise surface as a bare `WriteZero` or
+/// `UnexpectedEof` error from the benchmark loop.
+fn validate_fixed_workload(
+    capacity: usize,
     cycles: usize,
     write_size: usize,
     writes_per_cycle: usize,
     read_size: usize,
     reads_per_cycle: usize,
-) -> io::Result<Duration> {
-    // Initialize corpus
-    let corpus: Vec<u8> = (b'A'..=b'Z')
-        .chain(b'a'..=b'z')
-        .chain(b'0'..=b'9')
-        .cycle()
-        .take(write_size * writes_per_cycle * cycles)
-        .collect();
+) -> io::Result<()> {
+    if cycles == 0 {
+        return Ok(());
+    }
+    let written_per_cycle = write_size as u128 * writes_per_cycle as u128;
+    let read_per_cycle = read_size as u128 * reads_per_cycle as u128;
+    if read_per_cycle > written_per_cycle {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--read-size * --reads-per-cycle ({read_per_cycle}) exceeds --write-size * \
+                 --writes-per-cycle ({written_per_cycle}), so reads would run out of data"
+            ),
+        ));
+    }
+
+    // Unread data piles up by the difference every cycle, and peaks after the last writes
+    let peak = (cycles as u128 - 1) * (written_per_cycle - read_per_cycle) + written_per_cycle;
+    if peak > capacity as u128 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "capacity {capacity} is too small: --cycles {cycles} with --write-size \
+                 {write_size} * --writes-per-cycle {writes_per_cycle} and --read-size \
+                 {read_size} * --reads-per-cycle {reads_per_cycle} buffers up to {peak} bytes"
+            ),
+        ));
+    }
+    Ok(())
+}
 
+fn benchmark_byte_stream<S: Read + Write>(
+    mut byte_stream: S,
+    corpus: &[u8],
+    cycles: usize,
+    write_size: usize,
+    writes_per_cycle: usize,
+    read_size: usize,
+    reads_per_cycle: usize,
+) -> io::Result<Duration> {
     let mut read_buffer = vec![0u8; read_size * reads_per_cycle * cycles];
     let start_time = Instant::now();
 
@@ -83,44 +303,315 @@ fn benchmark_byte_stream(
 
     // Consistency check (outside of timed section)
     let expected_data: Vec<u8> = corpus
-        .into_iter()
+        .iter()
+        .copied()
         .cycle()
         .take(read_size * reads_per_cycle * cycles)
         .collect();
 
     if read_buffer != expected_data {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "Inconsistency detected in read data",
-        ));
+        return Err(FoamError::Inconsistency.into());
     }
 
     Ok(duration)
 }
 
+fn benchmark_byte_stream_random_sizes(
+    mut byte_stream: ByteStream,
+    corpus: &[u8],
+    write_sizes: &[usize],
+    writes_per_cycle: usize,
+    read_sizes: &[usize],
+    reads_per_cycle: usize,
+) -> io::Result<(Duration, usize, SizeHistogram)> {
+    let max_read_size = read_sizes.iter().copied().max().unwrap_or(0);
+    let mut read_buffer = vec![0u8; corpus.len() + max_read_size];
+    let mut written_sizes = Vec::with_capacity(write_sizes.len());
+    let mut read_sizes_achieved = Vec::with_capacity(read_sizes.len());
+    let mut bytes_written = 0;
+    let mut bytes_read = 0;
+    let start_time = Instant::now();
+
+    // Sizes are drawn up front, so each operation accepts whatever fits and the achieved sizes
+    // are recorded for the consistency check and histogram
+    for (writes, reads) in write_sizes
+        .chunks(writes_per_cycle.max(1))
+        .zip(read_sizes.chunks(reads_per_cycle.max(1)))
+    {
+        for &size in writes {
+            let written = byte_stream.write(&corpus[bytes_written..bytes_written + size])?;
+            bytes_written += written;
+            written_sizes.push(written);
+        }
+
+        for &size in reads {
+            let read = byte_stream.read(&mut read_buffer[bytes_read..bytes_read + size])?;
+            bytes_read += read;
+            read_sizes_achieved.push(read);
+        }
+    }
+
+    let duration = start_time.elapsed();
+
+    // Consistency check (outside of timed section)
+    if read_buffer[..bytes_read] != corpus[..bytes_read] {
+        return Err(FoamError::Inconsistency.into());
+    }
+
+    let mut histogram = SizeHistogram::default();
+    written_sizes
+        .into_iter()
+        .for_each(|size| histogram.record_write(size));
+    read_sizes_achieved
+        .into_iter()
+        .for_each(|size| histogram.record_read(size));
+
+    Ok((duration, bytes_read, histogram))
+}
+
+fn print_table(results: &[BenchmarkResult]) {
+    println!("{:>12} {:>14} {:>16}", "capacity", "elapsed", "throughput");
+    for result in results {
+        println!(
+            "{:>12} {:>14} {:>11.2} MiB/s",
+            result.capacity,
+            format!("{:?}", result.elapsed),
+            result.throughput_mib_s
+        );
+    }
+}
+
+fn print_comparison(results: &[BenchmarkResult]) {
+    println!("{:>12} {:>14} {:>16}", "backend", "elapsed", "throughput");
+    for result in results {
+        println!(
+            "{:>12} {:>14} {:>11.2} MiB/s",
+            result.backend.unwrap_or_default(),
+            format!("{:?}", result.elapsed),
+            result.throughput_mib_s
+        );
+    }
+}
+
+fn print_histograms(results: &[BenchmarkResult]) {
+    for result in results {
+        let Some(histogram) = &result.size_histogram else {
+            continue;
+        };
+        println!("Size histogram (capacity {}):", result.capacity);
+        println!("{:>12} {:>12} {:>12}", "size <=", "writes", "reads");
+        let buckets: std::collections::BTreeSet<_> = histogram
+            .writes
+            .keys()
+            .chain(histogram.reads.keys())
+            .collect();
+        for bucket in buckets {
+            println!(
+                "{:>12} {:>12} {:>12}",
+                bucket,
+                histogram.writes.get(bucket).copied().unwrap_or(0),
+                histogram.reads.get(bucket).copied().unwrap_or(0)
+            );
+        }
+    }
+}
+
 fn main() -> io::Result<()> {
     let args = Cli::parse();
-    match args.command {
+    let results = match &args.command {
         Command::ByteStream {
             capacity,
+            capacity_sweep,
             cycles,
             write_size,
             writes_per_cycle,
             read_size,
             reads_per_cycle,
+            write_size_dist,
+            read_size_dist,
+            seed,
         } => {
-            let byte_stream = ByteStream::new(capacity);
-            let duration = benchmark_byte_stream(
-                byte_stream,
-                cycles,
-                write_size,
-                writes_per_cycle,
-                read_size,
-                reads_per_cycle,
+            let capacities = capacity_sweep.clone().unwrap_or_else(|| vec![*capacity]);
+            let mut results = Vec::with_capacity(capacities.len());
+
+            if write_size_dist.is_some() || read_size_dist.is_some() {
+                let write_size_dist = write_size_dist.unwrap_or(SizeDist::Fixed(*write_size));
+                let read_size_dist = read_size_dist.unwrap_or(SizeDist::Fixed(*read_size));
+                let mut rng = StdRng::seed_from_u64(*seed);
+                let write_sizes: Vec<usize> = (0..cycles * writes_per_cycle)
+                    .map(|_| write_size_dist.sample(&mut rng))
+                    .collect();
+                let read_sizes: Vec<usize> = (0..cycles * reads_per_cycle)
+                    .map(|_| read_size_dist.sample(&mut rng))
+                    .collect();
+                let corpus = make_corpus(write_sizes.iter().sum());
+
+                for capacity in capacities {
+                    let byte_stream = ByteStream::new(capacity);
+                    let (duration, bytes, histogram) = benchmark_byte_stream_random_sizes(
+                        byte_stream,
+                        &corpus,
+                        &write_sizes,
+                        *writes_per_cycle,
+                        &read_sizes,
+                        *reads_per_cycle,
+                    )?;
+                    let mut result = BenchmarkResult::new(capacity, bytes, duration);
+                    result.size_histogram = Some(histogram);
+                    results.push(result);
+                }
+            } else {
+                let corpus = make_corpus(write_size * writes_per_cycle * cycles);
+                let bytes = read_size * reads_per_cycle * cycles;
+                for capacity in capacities {
+                    validate_fixed_workload(
+                        capacity,
+                        *cycles,
+                        *write_size,
+                        *writes_per_cycle,
+                        *read_size,
+                        *reads_per_cycle,
+                    )?;
+                    let byte_stream = ByteStream::new(capacity);
+                    let duration = benchmark_byte_stream(
+                        byte_stream,
+                        &corpus,
+                        *cycles,
+                        *write_size,
+                        *writes_per_cycle,
+                        *read_size,
+                        *reads_per_cycle,
+                    )?;
+                    results.push(BenchmarkResult::new(capacity, bytes, duration));
+                }
+            }
+            results
+        }
+        Command::Compare {
+            capacity,
+            cycles,
+            write_size,
+            writes_per_cycle,
+            read_size,
+            reads_per_cycle,
+        } => {
+            validate_fixed_workload(
+                *capacity,
+                *cycles,
+                *write_size,
+                *writes_per_cycle,
+                *read_size,
+                *reads_per_cycle,
+            )?;
+            let corpus = make_corpus(write_size * writes_per_cycle * cycles);
+            let bytes = read_size * reads_per_cycle * cycles;
+            let mut results = Vec::with_capacity(3);
+            let mut record = |backend: &'static str, duration: io::Result<Duration>| {
+                let duration = duration
+                    .map_err(|err| io::Error::new(err.kind(), format!("{backend}: {err}")))?;
+                let mut result = BenchmarkResult::new(*capacity, bytes, duration);
+                result.backend = Some(backend);
+                results.push(result);
+                Ok::<_, io::Error>(())
+            };
+            record(
+                "ByteStream",
+                benchmark_byte_stream(
+                    ByteStream::new(*capacity),
+                    &corpus,
+                    *cycles,
+                    *write_size,
+                    *writes_per_cycle,
+                    *read_size,
+                    *reads_per_cycle,
+                ),
+            )?;
+            record(
+                "VecDeque",
+                benchmark_byte_stream(
+                    VecDeque::<u8>::with_capacity(*capacity),
+                    &corpus,
+                    *cycles,
+                    *write_size,
+                    *writes_per_cycle,
+                    *read_size,
+                    *reads_per_cycle,
+                ),
+            )?;
+            record(
+                "Vec",
+                benchmark_byte_stream(
+                    ShiftingBuffer(Vec::with_capacity(*capacity)),
+                    &corpus,
+                    *cycles,
+                    *write_size,
+                    *writes_per_cycle,
+                    *read_size,
+                    *reads_per_cycle,
+                ),
             )?;
-            println!("Elapsed: {duration:?}");
+            results
         }
+    };
+
+    if args.json {
+        let report = Report {
+            command: &args.command,
+            results,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if let Command::Compare { .. } = args.command {
+        print_comparison(&results);
+    } else {
+        if let [result] = results.as_slice() {
+            println!("Elapsed: {:?}", result.elapsed);
+        } else {
+            print_table(&results);
+        }
+        print_histograms(&results);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{benchmark_byte_stream, make_corpus, validate_fixed_workload};
+    use rusty_foam::byte_stream::ByteStream;
+
+    #[test]
+    fn test_validate_fixed_workload() -> io::Result<()> {
+        validate_fixed_workload(4096, 1024, 1024, 1, 1024, 1)?;
+        validate_fixed_workload(4096, 3, 1024, 2, 512, 2)?;
+        validate_fixed_workload(16, 0, 1024, 1, 2048, 1)?;
+
+        let err = validate_fixed_workload(4096, 4, 1024, 1, 512, 3).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("--reads-per-cycle (1536)"));
+
+        let err = validate_fixed_workload(4096, 5, 1024, 2, 512, 2).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("capacity 4096 is too small"));
+        assert!(err.to_string().contains("up to 6144 bytes"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_feasible_workload_runs() -> io::Result<()> {
+        let (capacity, cycles, write_size, writes, read_size, reads) = (64, 8, 8, 2, 4, 3);
+        validate_fixed_workload(capacity, cycles, write_size, writes, read_size, reads)?;
+        let corpus = make_corpus(write_size * writes * cycles);
+        benchmark_byte_stream(
+            ByteStream::new(capacity),
+            &corpus,
+            cycles,
+            write_size,
+            writes,
+            read_size,
+            reads,
+        )?;
+        Ok(())
+    }
+}
@@ -1,203 +1,2377 @@
-use std::io::{self, Read, Write};
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, IoSlice, IoSliceMut, Read, Seek, SeekFrom, Write};
+use std::mem::MaybeUninit;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+#[cfg(feature = "linux")]
+use std::os::fd::RawFd;
+use std::ptr;
+use std::sync::{Mutex, PoisonError};
+
+use crate::error::FoamError;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Accept only as much of a write as fits, like a bounded pipe.
+    #[default]
+    Truncate,
+    /// Always accept the whole write, discarding the oldest unread bytes to make room.
+    OverwriteOldest,
+}
 
 pub struct ByteStream {
     capacity: usize,
     head_index: usize,
     used_capacity: usize,
     buffer: Vec<u8>,
+    overflow_policy: OverflowPolicy,
+    bytes_read: u64,
+    bytes_written: u64,
+    initial_offset: u64,
+    write_quota: Option<u64>,
+    wrap_count: u64,
+    // Behind a mutex only so the stream stays `Sync`; it is always reached through `&mut self`
+    read_tap: Option<Mutex<Box<dyn Write + Send>>>,
+    read_tap_failed: bool,
+    max_op_size: Option<NonZeroUsize>,
+}
+
+/// Read position saved by `ByteStream::save`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SavedPosition {
+    bytes_read: u64,
+    bytes_written: u64,
+}
+
+/// Configures a `ByteStream` before construction, see `ByteStream::builder`.
+#[derive(Clone, Debug)]
+pub struct ByteStreamBuilder {
+    capacity: usize,
+    overflow_policy: OverflowPolicy,
+    write_quota: Option<u64>,
+    initial_offset: u64,
+    max_op_size: Option<NonZeroUsize>,
+}
+
+impl ByteStreamBuilder {
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow_policy = policy;
+        self
+    }
+
+    pub fn write_quota(mut self, bytes: Option<u64>) -> Self {
+        self.write_quota = bytes;
+        self
+    }
+
+    pub fn initial_offset(mut self, offset: u64) -> Self {
+        self.initial_offset = offset;
+        self
+    }
+
+    pub fn max_op_size(mut self, size: Option<NonZeroUsize>) -> Self {
+        self.max_op_size = size;
+        self
+    }
+
+    pub fn build(self) -> ByteStream {
+        let mut byte_stream = ByteStream::with_initial_offset(self.capacity, self.initial_offset);
+        byte_stream.set_overflow_policy(self.overflow_policy);
+        byte_stream.set_write_quota(self.write_quota);
+        byte_stream.set_max_op_size(self.max_op_size);
+        byte_stream
+    }
 }
 
 impl ByteStream {
     pub fn new(capacity: usize) -> Self {
-        let buffer = vec![0; capacity];
-        Self {
+        Self::from_buffer(vec![0; capacity], 0)
+    }
+
+    pub fn builder(capacity: usize) -> ByteStreamBuilder {
+        ByteStreamBuilder {
             capacity,
+            overflow_policy: OverflowPolicy::default(),
+            write_quota: None,
+            initial_offset: 0,
+            max_op_size: None,
+        }
+    }
+
+    /// Creates a stream whose read and write counters start at `offset`, e.g. to line positions
+    /// up with a transfer resumed after `offset` bytes.
+    pub fn with_initial_offset(capacity: usize, offset: u64) -> Self {
+        let mut byte_stream = Self::new(capacity);
+        byte_stream.bytes_read = offset;
+        byte_stream.bytes_written = offset;
+        byte_stream.initial_offset = offset;
+        byte_stream
+    }
+
+    pub fn with_storage(storage: &mut [u8]) -> BorrowedByteStream<'_> {
+        BorrowedByteStream {
+            buffer: storage,
             head_index: 0,
             used_capacity: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.overflow_policy = policy;
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Caps the total number of bytes ever accepted by `write` at `bytes`, counted from the
+    /// creation of the stream. Once reached, writes return `Ok(0)` even if there is free space.
+    pub fn set_write_quota(&mut self, bytes: Option<u64>) {
+        self.write_quota = bytes;
+    }
+
+    pub fn remaining_quota(&self) -> Option<u64> {
+        self.write_quota
+            .map(|quota| quota.saturating_sub(self.bytes_written - self.initial_offset))
+    }
+
+    /// Copies every byte read out of the stream to `sink` as well, including bytes moved out by
+    /// `splice`, `drain_to`, `messages` and `BufRead::consume`. Bytes skipped by seeking forward
+    /// or dropped by `OverflowPolicy::OverwriteOldest` are not copied. Errors from `sink` do not
+    /// affect reads; they are reported by `read_tap_failed`.
+    pub fn set_read_tap(&mut self, sink: Box<dyn Write + Send>) {
+        self.read_tap = Some(Mutex::new(sink));
+        self.read_tap_failed = false;
+    }
+
+    pub fn remove_read_tap(&mut self) -> Option<Box<dyn Write + Send>> {
+        self.read_tap
+            .take()
+            .map(|tap| tap.into_inner().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Whether writing to the read tap has failed since it was set.
+    pub fn read_tap_failed(&self) -> bool {
+        self.read_tap_failed
+    }
+
+    /// Caps how many bytes a single `Read::read` or `Write::write` call transfers, e.g. to keep
+    /// one stream from monopolizing an event loop tick. Callers loop to transfer more. The cap is
+    /// nonzero so that a read with data buffered never returns `Ok(0)`, which means end of stream.
+    pub fn set_max_op_size(&mut self, size: Option<NonZeroUsize>) {
+        self.max_op_size = size;
+    }
+
+    pub fn max_op_size(&self) -> Option<NonZeroUsize> {
+        self.max_op_size
+    }
+
+    /// Number of reads and writes that had to wrap around the end of the buffer.
+    pub fn wrap_count(&self) -> u64 {
+        self.wrap_count
+    }
+
+    pub fn make_contiguous(&mut self) -> &[u8] {
+        if self.head_index + self.used_capacity > self.capacity {
+            self.buffer.rotate_left(self.head_index);
+            self.head_index = 0;
+        }
+        &self.buffer[self.head_index..self.head_index + self.used_capacity]
+    }
+
+    pub fn write_from_slices<'a, I: IntoIterator<Item = &'a [u8]>>(&mut self, slices: I) -> usize {
+        let mut total_written = 0;
+        for slice in slices {
+            let written = self.write_slice(slice);
+            total_written += written;
+            if written < slice.len() {
+                break;
+            }
+        }
+        total_written
+    }
+
+    /// Writes all of `bufs`, advancing them past written data, or fails with
+    /// `ErrorKind::WriteZero` once the stream stops accepting bytes.
+    pub fn write_all_vectored(&mut self, mut bufs: &mut [IoSlice<'_>]) -> io::Result<()> {
+        IoSlice::advance_slices(&mut bufs, 0);
+        while !bufs.is_empty() {
+            let written = self.write_from_slices(bufs.iter().map(|buf| &**buf));
+            if written == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ));
+            }
+            IoSlice::advance_slices(&mut bufs, written);
+        }
+        Ok(())
+    }
+
+    pub fn peek_u8(&self) -> Option<u8> {
+        self.peek_array().map(u8::from_be_bytes)
+    }
+
+    pub fn peek_u16_be(&self) -> Option<u16> {
+        self.peek_array().map(u16::from_be_bytes)
+    }
+
+    pub fn peek_u32_be(&self) -> Option<u32> {
+        self.peek_array().map(u32::from_be_bytes)
+    }
+
+    /// Calls `f` with the buffered data in logical order without consuming it: once with the
+    /// head-contiguous slice, then once more with the rest if the data wraps.
+    pub fn for_each_chunk<F: FnMut(&[u8])>(&self, mut f: F) {
+        let (lo, hi) = self.as_slices();
+        for chunk in [lo, hi] {
+            if !chunk.is_empty() {
+                f(chunk);
+            }
+        }
+    }
+
+    /// Like `read`, but into possibly uninitialized storage. Only the first `n` elements of `buf`
+    /// are written, where `n` is the returned count.
+    pub fn read_buf(&mut self, buf: &mut [MaybeUninit<u8>]) -> usize {
+        let (lo, hi) = self.as_slices();
+        let read_size = buf.len().min(self.used_capacity);
+        let lo_size = read_size.min(lo.len());
+        let dst = buf.as_mut_ptr().cast::<u8>();
+        // SAFETY: `MaybeUninit<u8>` has the layout of `u8`, both copies stay within
+        // `buf[..read_size]`, and the ring cannot overlap the caller's buffer
+        unsafe {
+            ptr::copy_nonoverlapping(lo.as_ptr(), dst, lo_size);
+            ptr::copy_nonoverlapping(hi.as_ptr(), dst.add(lo_size), read_size - lo_size);
+        }
+
+        self.consume_read(read_size);
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+        read_size
+    }
+
+    /// Fills `buf` completely and returns true, or consumes nothing and returns false if fewer
+    /// than `buf.len()` bytes are buffered.
+    pub fn read_exact_or_none(&mut self, buf: &mut [u8]) -> bool {
+        if self.used_capacity < buf.len() {
+            return false;
+        }
+        self.read_slice(buf);
+        true
+    }
+
+    /// Consumes and returns exactly `n` bytes, or consumes nothing and returns `None` if fewer
+    /// than `n` bytes are buffered.
+    pub fn consume_if_available(&mut self, n: usize) -> Option<Vec<u8>> {
+        if self.used_capacity < n {
+            return None;
+        }
+        let mut bytes = vec![0; n];
+        self.read_slice(&mut bytes);
+        Some(bytes)
+    }
+
+    /// Consumes the buffered bytes through the first `delim` and appends them to `out`, returning
+    /// how many were appended. Consumes nothing and returns `None` if `delim` is not buffered.
+    pub fn read_until_delim(&mut self, delim: u8, out: &mut Vec<u8>) -> Option<usize> {
+        let (lo, hi) = self.as_slices();
+        let size = lo.iter().chain(hi).position(|&byte| byte == delim)? + 1;
+        let start = out.len();
+        out.resize(start + size, 0);
+        self.read_slice(&mut out[start..]);
+        Some(size)
+    }
+
+    /// Suggests the size of the next read, scaled from `min` when empty to `max` when full by
+    /// how much of the capacity is in use.
+    ///
+    /// Panics if `min` is larger than `max`.
+    pub fn suggested_read_size(&self, min: usize, max: usize) -> usize {
+        assert!(min <= max, "minimum read size {min} exceeds maximum {max}");
+        if self.capacity == 0 {
+            return min;
+        }
+        let scaled = (max - min) as u128 * self.used_capacity as u128 / self.capacity as u128;
+        min + scaled as usize
+    }
+
+    /// Pushes `bytes` back in front of the buffered data, to be read again before it. Only the
+    /// trailing part of `bytes` that fits in the free space is pushed back, and no more than the
+    /// number of bytes read so far, so read positions stay consistent. Returns how many bytes
+    /// were pushed back.
+    pub fn unread(&mut self, bytes: &[u8]) -> usize {
+        let size = bytes
+            .len()
+            .min(self.capacity - self.used_capacity)
+            .min(usize::try_from(self.bytes_read - self.initial_offset).unwrap_or(usize::MAX));
+        if size == 0 {
+            return 0;
+        }
+
+        self.rewind(size);
+        if copy_into_ring(
+            &mut self.buffer,
+            self.head_index,
+            &bytes[bytes.len() - size..],
+        ) {
+            self.wrap_count += 1;
+        }
+        size
+    }
+
+    /// Writes the buffered data to `dst` with at most two `write` calls, one per contiguous
+    /// region, consuming only what `dst` accepted. Stops after a short write. An error is only
+    /// returned if nothing was drained; otherwise the bytes drained so far are reported.
+    pub fn drain_to<W: Write>(&mut self, dst: &mut W) -> io::Result<usize> {
+        let mut drained = 0;
+        while drained < self.used_capacity {
+            let start = (self.head_index + drained) % self.capacity;
+            let chunk_size = (self.used_capacity - drained).min(self.capacity - start);
+            let written = match dst.write(&self.buffer[start..start + chunk_size]) {
+                Ok(written) => written,
+                Err(err) if drained == 0 => return Err(err),
+                Err(_) => break,
+            };
+            drained += written;
+            if written < chunk_size {
+                break;
+            }
+        }
+        if drained > 0 {
+            self.consume_read(drained);
+        }
+        Ok(drained)
+    }
+
+    pub fn read_from<R: Read>(&mut self, reader: &mut R) -> io::Result<usize> {
+        let (free_region, _) = self.free_regions();
+        if free_region.is_empty() {
+            return Ok(0);
+        }
+
+        let read_size = reader.read(&mut self.buffer[free_region])?;
+        self.commit(read_size);
+        Ok(read_size)
+    }
+
+    /// Reads from `fd` straight into both free regions of the ring with a single `readv`.
+    #[cfg(feature = "linux")]
+    pub fn read_from_fd(&mut self, fd: RawFd) -> io::Result<usize> {
+        let (lo, hi) = self.free_regions();
+        if lo.is_empty() {
+            return Ok(0);
+        }
+
+        let (buffer_hi, buffer_lo) = self.buffer.split_at_mut(lo.start);
+        let iovecs = [
+            libc::iovec {
+                iov_base: buffer_lo.as_mut_ptr() as *mut libc::c_void,
+                iov_len: lo.len(),
+            },
+            libc::iovec {
+                iov_base: buffer_hi.as_mut_ptr() as *mut libc::c_void,
+                iov_len: hi.len(),
+            },
+        ];
+        let iovec_count = if hi.is_empty() { 1 } else { 2 };
+        let read_size = unsafe { libc::readv(fd, iovecs.as_ptr(), iovec_count) };
+        if read_size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        self.commit(read_size as usize);
+        Ok(read_size as usize)
+    }
+
+    /// Free regions of the ring in logical order, for vectored reads like `Read::read_vectored`.
+    /// The second slice is empty unless the free space wraps. Call `commit` with the number of
+    /// bytes filled.
+    pub fn free_io_slices(&mut self) -> [IoSliceMut<'_>; 2] {
+        let (lo, hi) = self.free_regions();
+        let (buffer_hi, buffer_lo) = self.buffer.split_at_mut(lo.start);
+        [
+            IoSliceMut::new(&mut buffer_lo[..lo.len()]),
+            IoSliceMut::new(&mut buffer_hi[..hi.len()]),
+        ]
+    }
+
+    /// Marks the first `size` bytes of the free regions as written.
+    ///
+    /// Panics if `size` is larger than the free space returned by `free_io_slices`.
+    pub fn commit(&mut self, size: usize) {
+        let (lo, hi) = self.free_regions();
+        assert!(
+            size <= lo.len() + hi.len(),
+            "commit of {size} bytes exceeds the free space"
+        );
+        if size > lo.len() {
+            self.wrap_count += 1;
+        }
+        self.used_capacity += size;
+        self.bytes_written += size as u64;
+    }
+
+    pub fn reader_view(&self) -> ReaderView<'_> {
+        ReaderView {
+            inner: self,
+            position: 0,
+        }
+    }
+
+    pub fn messages(&mut self) -> Messages<'_> {
+        Messages { inner: self }
+    }
+
+    pub fn into_vec_deque(mut self) -> VecDeque<u8> {
+        self.buffer.rotate_left(self.head_index);
+        self.buffer.truncate(self.used_capacity);
+        VecDeque::from(self.buffer)
+    }
+
+    pub fn check_invariants(&self) {
+        assert!(
+            self.used_capacity <= self.capacity,
+            "used capacity {} exceeds capacity {}",
+            self.used_capacity,
+            self.capacity
+        );
+        assert!(
+            self.capacity == 0 || self.head_index < self.capacity,
+            "head index {} out of bounds for capacity {}",
+            self.head_index,
+            self.capacity
+        );
+        assert_eq!(
+            self.buffer.len(),
+            self.capacity,
+            "buffer length does not match capacity"
+        );
+    }
+
+    pub fn save(&self) -> SavedPosition {
+        SavedPosition {
+            bytes_read: self.bytes_read,
+            bytes_written: self.bytes_written,
+        }
+    }
+
+    /// Rewinds the read position to `position`, making the bytes read since then readable again.
+    ///
+    /// Fails with `FoamError::DataOverwritten` if writes since the save have reused the space of
+    /// those bytes, or `FoamError::SavedPositionAhead` if `position` is ahead of the stream.
+    pub fn restore(&mut self, position: SavedPosition) -> Result<(), FoamError> {
+        let (Some(rewind_size), Some(_)) = (
+            self.bytes_read.checked_sub(position.bytes_read),
+            self.bytes_written.checked_sub(position.bytes_written),
+        ) else {
+            return Err(FoamError::SavedPositionAhead);
+        };
+        let rewind_size = match usize::try_from(rewind_size) {
+            Ok(rewind_size) if rewind_size <= self.retained_size() => rewind_size,
+            _ => return Err(FoamError::DataOverwritten),
+        };
+
+        self.rewind(rewind_size);
+        Ok(())
+    }
+
+    pub fn splice(src: &mut ByteStream, dst: &mut ByteStream, max: usize) -> usize {
+        let (dst_lo, dst_hi) = dst.free_regions();
+        let size = max.min(src.used_capacity).min(dst_lo.len() + dst_hi.len());
+        if size == 0 {
+            return 0;
+        }
+
+        // Copy up to three contiguous pieces, bounded by whichever ring wraps first
+        let mut moved = 0;
+        while moved < size {
+            let src_index = (src.head_index + moved) % src.capacity;
+            let dst_index = (dst_lo.start + moved) % dst.capacity;
+            let chunk_size = (size - moved)
+                .min(src.capacity - src_index)
+                .min(dst.capacity - dst_index);
+            dst.buffer[dst_index..dst_index + chunk_size]
+                .copy_from_slice(&src.buffer[src_index..src_index + chunk_size]);
+            moved += chunk_size;
+        }
+        src.consume_read(size);
+        dst.commit(size);
+        size
+    }
+
+    #[cfg(feature = "futures")]
+    pub fn into_chunk_stream(self) -> ChunkStream {
+        ChunkStream { inner: self }
+    }
+
+    #[cfg(feature = "linux")]
+    pub(crate) fn readiness(&self) -> (bool, bool) {
+        (self.used_capacity > 0, self.used_capacity < self.capacity)
+    }
+
+    /// Buffered data in logical order; the second slice is empty unless the data wraps.
+    fn as_slices(&self) -> (&[u8], &[u8]) {
+        let lo_size = self.used_capacity.min(self.capacity - self.head_index);
+        (
+            &self.buffer[self.head_index..self.head_index + lo_size],
+            &self.buffer[..self.used_capacity - lo_size],
+        )
+    }
+
+    fn peek_array<const N: usize>(&self) -> Option<[u8; N]> {
+        if self.used_capacity < N {
+            return None;
+        }
+        Some(std::array::from_fn(|i| {
+            self.buffer[(self.head_index + i) % self.capacity]
+        }))
+    }
+
+    fn read_slice(&mut self, buf: &mut [u8]) -> usize {
+        let read_size = buf.len().min(self.used_capacity);
+        copy_from_ring(&self.buffer, self.head_index, &mut buf[..read_size]);
+        self.consume_read(read_size);
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+        read_size
+    }
+
+    fn write_slice(&mut self, buf: &[u8]) -> usize {
+        let buf = match self.remaining_quota() {
+            Some(remaining_quota) => {
+                &buf[..buf
+                    .len()
+                    .min(usize::try_from(remaining_quota).unwrap_or(usize::MAX))]
+            }
+            None => buf,
+        };
+        let input_size = buf.len();
+        let buf = match self.overflow_policy {
+            OverflowPolicy::Truncate => buf,
+            OverflowPolicy::OverwriteOldest => {
+                let overflow = (self.used_capacity + buf.len()).saturating_sub(self.capacity);
+                if overflow > 0 {
+                    self.discard(overflow.min(self.used_capacity));
+                }
+                // Bytes that would be overwritten by the same write count as discarded too
+                let skipped_size = buf.len().saturating_sub(self.capacity);
+                self.bytes_read += skipped_size as u64;
+                &buf[skipped_size..]
+            }
+        };
+
+        let capacity_left = self.capacity - self.used_capacity;
+        let write_size = buf.len().min(capacity_left);
+        let tail_index = (self.head_index + self.used_capacity) % self.capacity;
+        if copy_into_ring(&mut self.buffer, tail_index, &buf[..write_size]) {
+            self.wrap_count += 1;
+        }
+
+        self.used_capacity += write_size;
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+        let accepted_size = match self.overflow_policy {
+            OverflowPolicy::Truncate => write_size,
+            OverflowPolicy::OverwriteOldest => input_size,
+        };
+        self.bytes_written += accepted_size as u64;
+        accepted_size
+    }
+
+    fn from_buffer(buffer: Vec<u8>, used_capacity: usize) -> Self {
+        Self {
+            capacity: buffer.len(),
+            head_index: 0,
+            used_capacity,
             buffer,
+            overflow_policy: OverflowPolicy::default(),
+            bytes_read: 0,
+            bytes_written: 0,
+            initial_offset: 0,
+            write_quota: None,
+            wrap_count: 0,
+            read_tap: None,
+            read_tap_failed: false,
+            max_op_size: None,
+        }
+    }
+
+    #[cfg(test)]
+    fn set_head_index(&mut self, head_index: usize) {
+        self.head_index = head_index;
+    }
+
+    /// Free regions in logical order (the second is empty unless the free space wraps), limited
+    /// by the write quota.
+    fn free_regions(&self) -> (Range<usize>, Range<usize>) {
+        let writable_size = match self.remaining_quota() {
+            Some(remaining_quota) => (self.capacity - self.used_capacity)
+                .min(usize::try_from(remaining_quota).unwrap_or(usize::MAX)),
+            None => self.capacity - self.used_capacity,
+        };
+        if writable_size == 0 {
+            return (0..0, 0..0);
+        }
+
+        let tail_index = (self.head_index + self.used_capacity) % self.capacity;
+        let lo_size = writable_size.min(self.capacity - tail_index);
+        (tail_index..tail_index + lo_size, 0..writable_size - lo_size)
+    }
+
+    /// Number of already-read bytes before the head that have not been reused by writes.
+    fn retained_size(&self) -> usize {
+        (self.capacity - self.used_capacity)
+            .min(usize::try_from(self.bytes_read - self.initial_offset).unwrap_or(usize::MAX))
+    }
+
+    fn rewind(&mut self, size: usize) {
+        self.head_index = (self.head_index + self.capacity - size) % self.capacity;
+        self.used_capacity += size;
+        self.bytes_read -= size as u64;
+    }
+
+    /// Copies the first `size` buffered bytes to the read tap, if any.
+    fn tap_read(&mut self, size: usize) {
+        let Some(tap) = &mut self.read_tap else {
+            return;
+        };
+        let tap = tap.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let lo_size = size.min(self.capacity - self.head_index);
+        let result = tap
+            .write_all(&self.buffer[self.head_index..self.head_index + lo_size])
+            .and_then(|()| tap.write_all(&self.buffer[..size - lo_size]));
+        if result.is_err() {
+            self.read_tap_failed = true;
+        }
+    }
+
+    /// Consumes the first `size` buffered bytes as read: copies them to the read tap, counts a
+    /// wrap if they cross the end of the buffer, and advances the head.
+    fn consume_read(&mut self, size: usize) {
+        self.tap_read(size);
+        if size > self.capacity - self.head_index {
+            self.wrap_count += 1;
+        }
+        self.discard(size);
+    }
+
+    fn discard(&mut self, size: usize) {
+        self.used_capacity -= size;
+        self.head_index = (self.head_index + size) % self.capacity;
+        self.bytes_read += size as u64;
+    }
+}
+
+impl Read for ByteStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = buf
+            .len()
+            .min(self.max_op_size.map_or(usize::MAX, NonZeroUsize::get));
+        Ok(self.read_slice(&mut buf[..size]))
+    }
+}
+
+/// Moves the read position within the retained window: back over already-read bytes that have
+/// not been reused by writes, or forward over buffered bytes. Positions are counted in bytes read
+/// over the lifetime of the stream.
+impl Seek for ByteStream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let end = self.bytes_read + self.used_capacity as u64;
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.bytes_read.checked_add_signed(offset),
+            SeekFrom::End(offset) => end.checked_add_signed(offset),
+        };
+        let oldest = self.bytes_read - self.retained_size() as u64;
+
+        match target {
+            Some(target) if target < oldest || target > end => {
+                Err(FoamError::SeekOutOfRange.into())
+            }
+            Some(target) => {
+                if target < self.bytes_read {
+                    self.rewind((self.bytes_read - target) as usize);
+                } else if target > self.bytes_read {
+                    self.discard((target - self.bytes_read) as usize);
+                }
+                Ok(self.bytes_read)
+            }
+            None => Err(FoamError::InvalidSeek.into()),
+        }
+    }
+}
+
+impl BufRead for ByteStream {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let chunk_size = self.used_capacity.min(self.capacity - self.head_index);
+        Ok(&self.buffer[self.head_index..self.head_index + chunk_size])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = amt.min(self.used_capacity);
+        self.consume_read(amt);
+    }
+}
+
+impl Write for ByteStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let size = buf
+            .len()
+            .min(self.max_op_size.map_or(usize::MAX, NonZeroUsize::get));
+        Ok(self.write_slice(&buf[..size]))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Copies `dst.len()` bytes out of `ring` starting at `start`, wrapping around the end. Returns
+/// whether the copy wrapped.
+fn copy_from_ring(ring: &[u8], start: usize, dst: &mut [u8]) -> bool {
+    let lo_size = dst.len().min(ring.len() - start);
+    let (dst_lo, dst_hi) = dst.split_at_mut(lo_size);
+    dst_lo.copy_from_slice(&ring[start..start + lo_size]);
+    dst_hi.copy_from_slice(&ring[..dst_hi.len()]);
+    !dst_hi.is_empty()
+}
+
+/// Copies `src` into `ring` starting at `start`, wrapping around the end. Returns whether the
+/// copy wrapped.
+fn copy_into_ring(ring: &mut [u8], start: usize, src: &[u8]) -> bool {
+    let lo_size = src.len().min(ring.len() - start);
+    let (src_lo, src_hi) = src.split_at(lo_size);
+    ring[start..start + lo_size].copy_from_slice(src_lo);
+    ring[..src_hi.len()].copy_from_slice(src_hi);
+    !src_hi.is_empty()
+}
+
+/// Ring buffer over caller-provided storage, created by `ByteStream::with_storage`.
+///
+/// Behaves like a `ByteStream` with the default truncating overflow policy, but never allocates.
+pub struct BorrowedByteStream<'a> {
+    buffer: &'a mut [u8],
+    head_index: usize,
+    used_capacity: usize,
+}
+
+impl BorrowedByteStream<'_> {
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+impl Read for BorrowedByteStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read_size = buf.len().min(self.used_capacity);
+        if read_size == 0 {
+            return Ok(0);
+        }
+
+        copy_from_ring(self.buffer, self.head_index, &mut buf[..read_size]);
+        self.used_capacity -= read_size;
+        self.head_index = (self.head_index + read_size) % self.buffer.len();
+        Ok(read_size)
+    }
+}
+
+impl Write for BorrowedByteStream<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let write_size = buf.len().min(self.buffer.len() - self.used_capacity);
+        if write_size == 0 {
+            return Ok(0);
+        }
+
+        let tail_index = (self.head_index + self.used_capacity) % self.buffer.len();
+        copy_into_ring(self.buffer, tail_index, &buf[..write_size]);
+        self.used_capacity += write_size;
+        Ok(write_size)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Streams are equal when their buffered bytes are equal in logical order, regardless of
+/// capacity, wrap position, or configuration.
+impl PartialEq for ByteStream {
+    fn eq(&self, other: &Self) -> bool {
+        let (lo, hi) = self.as_slices();
+        let (other_lo, other_hi) = other.as_slices();
+        self.used_capacity == other.used_capacity
+            && lo.iter().chain(hi).eq(other_lo.iter().chain(other_hi))
+    }
+}
+
+impl Eq for ByteStream {}
+
+impl Hash for ByteStream {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Feed the hasher fixed-size chunks of the logical contents, so the result does not
+        // depend on where the data wraps
+        const CHUNK_SIZE: usize = 256;
+        let (lo, hi) = self.as_slices();
+        let mut chunk = [0; CHUNK_SIZE];
+        let mut chunk_size = 0;
+        state.write_usize(self.used_capacity);
+        for &byte in lo.iter().chain(hi) {
+            chunk[chunk_size] = byte;
+            chunk_size += 1;
+            if chunk_size == CHUNK_SIZE {
+                state.write(&chunk);
+                chunk_size = 0;
+            }
         }
+        state.write(&chunk[..chunk_size]);
+    }
+}
+
+/// Takes over the deque's allocation, so the resulting capacity is `VecDeque::capacity`, or 1 for
+/// a deque that has not allocated.
+impl From<VecDeque<u8>> for ByteStream {
+    fn from(deque: VecDeque<u8>) -> Self {
+        let capacity = deque.capacity().max(1);
+        let used_capacity = deque.len();
+        let mut buffer = Vec::from(deque);
+        buffer.resize(capacity, 0);
+        Self::from_buffer(buffer, used_capacity)
+    }
+}
+
+/// Appends bytes until the stream stops accepting them, mirroring `write`: iteration stops once
+/// the write quota is used up or, under `OverflowPolicy::Truncate`, once there is no space left.
+/// Bytes that are not accepted are never pulled from the iterator.
+impl Extend<u8> for ByteStream {
+    fn extend<T: IntoIterator<Item = u8>>(&mut self, iter: T) {
+        let mut iter = iter.into_iter();
+        while self.remaining_quota() != Some(0)
+            && (self.overflow_policy == OverflowPolicy::OverwriteOldest
+                || self.used_capacity < self.capacity)
+        {
+            let Some(byte) = iter.next() else {
+                break;
+            };
+            self.write_slice(&[byte]);
+        }
+    }
+}
+
+impl<'a> Extend<&'a u8> for ByteStream {
+    fn extend<T: IntoIterator<Item = &'a u8>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+/// Read-only `Read` over the data buffered in a `ByteStream` when the view was created, leaving
+/// the stream itself untouched.
+pub struct ReaderView<'a> {
+    inner: &'a ByteStream,
+    position: usize,
+}
+
+impl Read for ReaderView<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read_size = buf.len().min(self.inner.used_capacity - self.position);
+        if read_size == 0 {
+            return Ok(0);
+        }
+
+        let start = (self.inner.head_index + self.position) % self.inner.capacity;
+        copy_from_ring(&self.inner.buffer, start, &mut buf[..read_size]);
+        self.position += read_size;
+        Ok(read_size)
+    }
+}
+
+/// Iterator over complete length-delimited messages (a big-endian `u32` length followed by the
+/// payload) at the front of a `ByteStream`.
+///
+/// Yields `None` as soon as the next message is not fully buffered, leaving any partial message
+/// in place so iteration can resume after more data is written. A message whose length exceeds
+/// the remaining capacity can never complete.
+pub struct Messages<'a> {
+    inner: &'a mut ByteStream,
+}
+
+impl Iterator for Messages<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let length = self.inner.peek_u32_be()? as usize;
+        if self.inner.used_capacity - 4 < length {
+            return None;
+        }
+
+        self.inner.consume_read(4);
+        let mut message = vec![0; length];
+        self.inner.read_slice(&mut message);
+        Some(message)
+    }
+}
+
+/// Stream of the buffered data as `Bytes` chunks, ending once the ring is drained.
+///
+/// Since the stream owns the `ByteStream`, nothing can be written after conversion, so an
+/// empty buffer means end of stream.
+#[cfg(feature = "futures")]
+pub struct ChunkStream {
+    inner: ByteStream,
+}
+
+#[cfg(feature = "futures")]
+impl futures_core::Stream for ChunkStream {
+    type Item = bytes::Bytes;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        let inner = &mut self.get_mut().inner;
+        if inner.used_capacity == 0 {
+            return std::task::Poll::Ready(None);
+        }
+
+        let chunk_size = inner.used_capacity.min(inner.capacity - inner.head_index);
+        let chunk = bytes::Bytes::copy_from_slice(
+            &inner.buffer[inner.head_index..inner.head_index + chunk_size],
+        );
+        inner.tap_read(chunk_size);
+        inner.discard(chunk_size);
+        std::task::Poll::Ready(Some(chunk))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unused_io_amount)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::VecDeque;
+    use std::hash::{Hash, Hasher};
+    use std::io::{self, BufRead, IoSlice, Read, Seek, SeekFrom, Write};
+    use std::mem::MaybeUninit;
+    use std::num::NonZeroUsize;
+    use std::sync::{Arc, Mutex};
+
+    use super::{ByteStream, OverflowPolicy};
+    use crate::error::FoamError;
+
+    #[test]
+    fn test_simple() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(1024);
+        let input_string = b"abcdefgh".repeat(128);
+
+        byte_stream.write(&input_string)?;
+        let mut read_buf = [0; 1024];
+        byte_stream.read(&mut read_buf)?;
+        assert_eq!(input_string, read_buf);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_truncation() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(1024);
+        let input_string = b"abcdefgh".repeat(128);
+
+        byte_stream.write(&input_string)?;
+        let mut read_buf = [0; 128];
+        byte_stream.read(&mut read_buf)?;
+        assert_eq!(input_string[0..128], read_buf);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_truncation() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(128);
+        let input_string = b"abcdefgh".repeat(128);
+
+        byte_stream.write(&input_string)?;
+        let mut read_buf = [0; 128];
+        byte_stream.read(&mut read_buf)?;
+        assert_eq!(input_string[0..128], read_buf);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_read_multiple_times() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(16);
+        byte_stream.write(b"Hello")?;
+        byte_stream.write(b" ")?;
+        byte_stream.write(b"World")?;
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+
+        assert_eq!(result, "Hello World");
+        Ok(())
+    }
+
+    #[test]
+    fn test_circular_buffer_behavior() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345678")?;
+
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+        assert_eq!(&buf, b"1234");
+
+        byte_stream.write(b"ABCD")?;
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+
+        assert_eq!(result, "5678ABCD");
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_reads() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(16);
+        byte_stream.write(b"Hello World")?;
+
+        let mut buf = [0; 5];
+        byte_stream.read(&mut buf)?;
+        assert_eq!(&buf, b"Hello");
+
+        byte_stream.read(&mut buf)?;
+        assert_eq!(&buf, b" Worl");
+
+        byte_stream.read(&mut buf)?;
+        assert_eq!(&buf[0..1], b"d");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_capacity_and_overflow() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        assert_eq!(byte_stream.capacity(), 8);
+
+        let write_result = byte_stream.write(b"123456789")?;
+        assert_eq!(write_result, 8); // Only 8 bytes should be written
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "12345678");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_truncation_across_wrap() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+
+        let write_result = byte_stream.write(b"ABCDEFGHIJ")?;
+        assert_eq!(write_result, 6);
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "56ABCDEF");
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_reads() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        let mut buf = [0; 4];
+        let read_result = byte_stream.read(&mut buf)?;
+        assert_eq!(read_result, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_writes() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345678")?;
+        let write_result = byte_stream.write(b"9")?;
+        assert_eq!(write_result, 0); // No space left, should write 0 bytes
+        Ok(())
+    }
+
+    #[test]
+    fn test_make_contiguous_not_wrapped() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+
+        let mut buf = [0; 2];
+        byte_stream.read(&mut buf)?;
+        assert_eq!(byte_stream.make_contiguous(), b"3456");
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "3456");
+        Ok(())
+    }
+
+    #[test]
+    fn test_make_contiguous_wrapped() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345678")?;
+
+        let mut buf = [0; 5];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"ABCD")?;
+        assert_eq!(byte_stream.make_contiguous(), b"678ABCD");
+
+        byte_stream.write(b"E")?;
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "678ABCDE");
+        Ok(())
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn test_chunk_stream() -> io::Result<()> {
+        use futures::StreamExt;
+
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345678")?;
+        let mut buf = [0; 5];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"ABCD")?;
+
+        let chunks: Vec<bytes::Bytes> =
+            futures::executor::block_on(byte_stream.into_chunk_stream().collect());
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks.concat(), b"678ABCD");
+        Ok(())
+    }
+
+    #[test]
+    fn test_splice_bounded_by_src() -> io::Result<()> {
+        let mut src = ByteStream::new(8);
+        let mut dst = ByteStream::new(16);
+        src.write(b"12345678")?;
+        let mut buf = [0; 6];
+        src.read(&mut buf)?;
+        src.write(b"ABCD")?;
+
+        assert_eq!(ByteStream::splice(&mut src, &mut dst, 100), 6);
+        assert_eq!(ByteStream::splice(&mut src, &mut dst, 100), 0);
+
+        let mut result = String::new();
+        dst.read_to_string(&mut result)?;
+        assert_eq!(result, "78ABCD");
+        Ok(())
+    }
+
+    #[test]
+    fn test_splice_bounded_by_dst() -> io::Result<()> {
+        let mut src = ByteStream::new(16);
+        let mut dst = ByteStream::new(8);
+        src.write(b"Hello World")?;
+        dst.write(b"123456")?;
+        let mut buf = [0; 4];
+        dst.read(&mut buf)?;
+
+        assert_eq!(ByteStream::splice(&mut src, &mut dst, 100), 6);
+        assert_eq!(ByteStream::splice(&mut src, &mut dst, 100), 0);
+
+        let mut result = String::new();
+        dst.read_to_string(&mut result)?;
+        assert_eq!(result, "56Hello ");
+        result.clear();
+        src.read_to_string(&mut result)?;
+        assert_eq!(result, "World");
+        Ok(())
+    }
+
+    #[test]
+    fn test_splice_bounded_by_max() -> io::Result<()> {
+        let mut src = ByteStream::new(16);
+        let mut dst = ByteStream::new(16);
+        src.write(b"Hello World")?;
+
+        assert_eq!(ByteStream::splice(&mut src, &mut dst, 5), 5);
+
+        let mut result = String::new();
+        dst.read_to_string(&mut result)?;
+        assert_eq!(result, "Hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_overwrite_oldest() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.set_overflow_policy(OverflowPolicy::OverwriteOldest);
+        byte_stream.write(b"123456")?;
+
+        let write_result = byte_stream.write(b"ABCDE")?;
+        assert_eq!(write_result, 5);
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "456ABCDE");
+        Ok(())
+    }
+
+    #[test]
+    fn test_overwrite_oldest_wrapped() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.set_overflow_policy(OverflowPolicy::OverwriteOldest);
+        byte_stream.write(b"12345678")?;
+
+        let mut buf = [0; 3];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"ABCDEF")?;
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "78ABCDEF");
+        Ok(())
+    }
+
+    #[test]
+    fn test_overwrite_oldest_larger_than_capacity() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.set_overflow_policy(OverflowPolicy::OverwriteOldest);
+        byte_stream.write(b"xyz")?;
+
+        let write_result = byte_stream.write(b"abcdefghijkl")?;
+        assert_eq!(write_result, 12);
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "efghijkl");
+        Ok(())
+    }
+
+    #[test]
+    fn test_peek_integers() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        assert_eq!(byte_stream.peek_u8(), None);
+
+        byte_stream.write(&[0x12, 0x34, 0x56])?;
+        assert_eq!(byte_stream.peek_u8(), Some(0x12));
+        assert_eq!(byte_stream.peek_u16_be(), Some(0x1234));
+        assert_eq!(byte_stream.peek_u32_be(), None);
+
+        byte_stream.write(&[0x78])?;
+        assert_eq!(byte_stream.peek_u32_be(), Some(0x12345678));
+
+        let mut buf = [0; 4];
+        assert_eq!(byte_stream.read(&mut buf)?, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_peek_integers_across_wrap() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 6];
+        byte_stream.read(&mut buf)?;
+
+        byte_stream.write(&[0xde, 0xad, 0xbe, 0xef])?;
+        assert_eq!(byte_stream.peek_u16_be(), Some(0xdead));
+        assert_eq!(byte_stream.peek_u32_be(), Some(0xdeadbeef));
+
+        let mut buf = [0; 1];
+        byte_stream.read(&mut buf)?;
+        assert_eq!(byte_stream.peek_u16_be(), Some(0xadbe));
+        assert_eq!(byte_stream.peek_u32_be(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_from_slices() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(16);
+        let slices: Vec<&[u8]> = vec![b"Hello", b" ", b"World"];
+        assert_eq!(byte_stream.write_from_slices(slices), 11);
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "Hello World");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_from_slices_fills_mid_slice() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        let slices: [&[u8]; 3] = [b"12345", b"ABCDE", b"xyz"];
+        assert_eq!(byte_stream.write_from_slices(slices), 8);
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "12345ABC");
+        Ok(())
+    }
+
+    #[test]
+    fn test_messages() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(32);
+        byte_stream.write(&[0, 0, 0, 5])?;
+        byte_stream.write(b"Hello")?;
+        byte_stream.write(&[0, 0, 0, 0])?;
+        byte_stream.write(&[0, 0, 0, 6])?;
+        byte_stream.write(b" Wor")?;
+
+        let messages: Vec<Vec<u8>> = byte_stream.messages().collect();
+        assert_eq!(messages, vec![b"Hello".to_vec(), Vec::new()]);
+        assert_eq!(byte_stream.peek_u32_be(), Some(6));
+
+        byte_stream.write(b"ld")?;
+        let messages: Vec<Vec<u8>> = byte_stream.messages().collect();
+        assert_eq!(messages, vec![b" World".to_vec()]);
+        assert_eq!(byte_stream.peek_u8(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_messages_across_wrap() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 6];
+        byte_stream.read(&mut buf)?;
+
+        byte_stream.write(&[0, 0, 0, 3])?;
+        byte_stream.write(b"abc")?;
+        let mut messages = byte_stream.messages();
+        assert_eq!(messages.next(), Some(b"abc".to_vec()));
+        assert_eq!(messages.next(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_buf_read() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345678")?;
+        let mut buf = [0; 5];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"AB\nC")?;
+
+        assert_eq!(byte_stream.fill_buf()?, b"678");
+        byte_stream.consume(1);
+        let mut line = String::new();
+        byte_stream.read_line(&mut line)?;
+        assert_eq!(line, "78AB\n");
+        assert_eq!(byte_stream.fill_buf()?, b"C");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_from() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+
+        let mut source = io::Cursor::new(b"ABCDEFGH".to_vec());
+        assert_eq!(byte_stream.read_from(&mut source)?, 2);
+        assert_eq!(byte_stream.read_from(&mut source)?, 4);
+        assert_eq!(byte_stream.read_from(&mut source)?, 0);
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "56ABCDEF");
+        Ok(())
+    }
+
+    #[test]
+    fn test_extend() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(16);
+        byte_stream.extend(b"Hello");
+        byte_stream.extend(
+            b" World!"
+                .iter()
+                .copied()
+                .filter(|b| b.is_ascii_alphabetic()),
+        );
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "HelloWorld");
+        Ok(())
+    }
+
+    #[test]
+    fn test_extend_overflow() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(4);
+        let mut iter = b"abcdef".iter();
+        byte_stream.extend(&mut iter);
+        assert_eq!(iter.next(), Some(&b'e'));
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "abcd");
+
+        byte_stream.set_overflow_policy(OverflowPolicy::OverwriteOldest);
+        byte_stream.extend(b"abcdef");
+        result.clear();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "cdef");
+
+        byte_stream.set_overflow_policy(OverflowPolicy::Truncate);
+        byte_stream.set_write_quota(Some(byte_stream.bytes_written() + 2));
+        let mut iter = b"abcd".iter();
+        byte_stream.extend(&mut iter);
+        assert_eq!(iter.next(), Some(&b'c'));
+        result.clear();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "ab");
+        Ok(())
+    }
+
+    #[test]
+    fn test_vec_deque_round_trip() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345678")?;
+        let mut buf = [0; 5];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"ABC")?;
+
+        let deque = byte_stream.into_vec_deque();
+        assert_eq!(deque, VecDeque::from(b"678ABC".to_vec()));
+
+        let mut byte_stream = ByteStream::from(deque);
+        assert!(byte_stream.capacity() >= 6);
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "678ABC");
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_vec_deque() -> io::Result<()> {
+        let mut deque = VecDeque::with_capacity(8);
+        deque.extend(b"xx12345");
+        deque.drain(..2);
+        deque.push_front(b'0');
+
+        let mut byte_stream = ByteStream::from(deque);
+        assert!(byte_stream.capacity() >= 8);
+        byte_stream.write(b"67")?;
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "01234567");
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_empty_vec_deque() -> io::Result<()> {
+        let mut byte_stream = ByteStream::from(VecDeque::new());
+        assert_eq!(byte_stream.capacity(), 1);
+        assert_eq!(byte_stream.read(&mut [0; 4])?, 0);
+        assert_eq!(byte_stream.write(b"12")?, 1);
+        assert_eq!(byte_stream.peek_u8(), Some(b'1'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_invariants() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.check_invariants();
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"ABCD")?;
+        byte_stream.check_invariants();
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "head index 8 out of bounds")]
+    fn test_check_invariants_detects_corruption() {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.set_head_index(8);
+        byte_stream.check_invariants();
+    }
+
+    #[test]
+    fn test_write_quota() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(16);
+        assert_eq!(byte_stream.remaining_quota(), None);
+        byte_stream.write(b"abc")?;
+        byte_stream.set_write_quota(Some(8));
+        assert_eq!(byte_stream.remaining_quota(), Some(5));
+
+        assert_eq!(byte_stream.write(b"defg")?, 4);
+        assert_eq!(byte_stream.remaining_quota(), Some(1));
+        assert_eq!(byte_stream.write(b"hij")?, 1);
+        assert_eq!(byte_stream.remaining_quota(), Some(0));
+        assert_eq!(byte_stream.write(b"k")?, 0);
+        assert_eq!(byte_stream.bytes_written(), 8);
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "abcdefgh");
+        assert_eq!(byte_stream.write(b"k")?, 0);
+
+        byte_stream.set_write_quota(None);
+        assert_eq!(byte_stream.write(b"k")?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_quota_below_bytes_written() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(16);
+        byte_stream.write(b"Hello World")?;
+        byte_stream.set_write_quota(Some(4));
+        assert_eq!(byte_stream.remaining_quota(), Some(0));
+        assert_eq!(byte_stream.write(b"!")?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_quota_applies_to_read_from_and_splice() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(16);
+        byte_stream.set_write_quota(Some(6));
+
+        let mut source = io::Cursor::new(b"abcd".to_vec());
+        assert_eq!(byte_stream.read_from(&mut source)?, 4);
+        let mut src = ByteStream::new(8);
+        src.write(b"efgh")?;
+        assert_eq!(ByteStream::splice(&mut src, &mut byte_stream, 100), 2);
+        assert_eq!(byte_stream.remaining_quota(), Some(0));
+        assert_eq!(byte_stream.bytes_written(), 6);
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "abcdef");
+        Ok(())
+    }
+
+    #[cfg(feature = "linux")]
+    #[test]
+    fn test_read_from_fd() -> io::Result<()> {
+        use std::os::fd::AsRawFd;
+        use std::os::unix::net::UnixStream;
+
+        let (mut sender, receiver) = UnixStream::pair()?;
+        let mut byte_stream = ByteStream::new(16);
+        sender.write_all(b"Hello")?;
+        assert_eq!(byte_stream.read_from_fd(receiver.as_raw_fd())?, 5);
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "Hello");
+        Ok(())
+    }
+
+    #[cfg(feature = "linux")]
+    #[test]
+    fn test_read_from_fd_wrapped_free_region() -> io::Result<()> {
+        use std::os::fd::AsRawFd;
+        use std::os::unix::net::UnixStream;
+
+        let (mut sender, receiver) = UnixStream::pair()?;
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+
+        sender.write_all(b"ABCDEFGHIJ")?;
+        assert_eq!(byte_stream.read_from_fd(receiver.as_raw_fd())?, 6);
+        assert_eq!(byte_stream.read_from_fd(receiver.as_raw_fd())?, 0);
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "56ABCDEF");
+
+        assert_eq!(byte_stream.read_from_fd(receiver.as_raw_fd())?, 4);
+        result.clear();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "GHIJ");
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_restore() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345678")?;
+        let mut buf = [0; 3];
+        byte_stream.read(&mut buf)?;
+
+        let position = byte_stream.save();
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+        assert_eq!(&buf, b"4567");
+        byte_stream.write(b"AB")?;
+        byte_stream.restore(position)?;
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "45678AB");
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_after_overwrite() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+
+        let position = byte_stream.save();
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"AB")?;
+        byte_stream.restore(position)?;
+        assert_eq!(byte_stream.peek_u8(), Some(b'1'));
+
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"CDE")?;
+        assert_eq!(
+            byte_stream.restore(position),
+            Err(FoamError::DataOverwritten)
+        );
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "56ABCDE");
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_survives_make_contiguous() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345678")?;
+        let mut buf = [0; 5];
+        byte_stream.read(&mut buf)?;
+
+        let position = byte_stream.save();
+        byte_stream.read(&mut buf[..1])?;
+        byte_stream.write(b"AB")?;
+        assert_eq!(byte_stream.make_contiguous(), b"78AB");
+        byte_stream.restore(position)?;
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "678AB");
+        Ok(())
+    }
+
+    #[test]
+    fn test_seek_backward() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+
+        assert_eq!(byte_stream.seek(SeekFrom::Current(-2))?, 2);
+        byte_stream.read(&mut buf[..2])?;
+        assert_eq!(&buf[..2], b"34");
+        assert_eq!(byte_stream.stream_position()?, 4);
+
+        byte_stream.write(b"ABCD")?;
+        assert_eq!(byte_stream.seek(SeekFrom::Current(-2))?, 2);
+        assert!(byte_stream.seek(SeekFrom::Current(-1)).is_err());
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "3456ABCD");
+        Ok(())
+    }
+
+    #[test]
+    fn test_seek_forward() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+
+        assert_eq!(byte_stream.seek(SeekFrom::Current(2))?, 2);
+        assert_eq!(byte_stream.seek(SeekFrom::End(-1))?, 5);
+        assert_eq!(byte_stream.seek(SeekFrom::Start(3))?, 3);
+        assert!(byte_stream.seek(SeekFrom::Current(4)).is_err());
+        assert!(byte_stream.seek(SeekFrom::End(1)).is_err());
+        assert!(byte_stream.seek(SeekFrom::Current(-4)).is_err());
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "456");
+        Ok(())
+    }
+
+    #[test]
+    fn test_borrowed_storage() -> io::Result<()> {
+        let mut storage = [0u8; 64];
+        let mut byte_stream = ByteStream::with_storage(&mut storage);
+        assert_eq!(byte_stream.capacity(), 64);
+
+        let input = b"abcdefgh".repeat(5);
+        assert_eq!(byte_stream.write(&input)?, 40);
+        let mut buf = [0; 32];
+        byte_stream.read_exact(&mut buf)?;
+        assert_eq!(buf, input[..32]);
+
+        let input = b"ABCDEFGH".repeat(8);
+        assert_eq!(byte_stream.write(&input)?, 56);
+        let mut result = Vec::new();
+        byte_stream.read_to_end(&mut result)?;
+        assert_eq!(result, [b"abcdefgh".as_slice(), &input[..56]].concat());
+        assert_eq!(byte_stream.read(&mut buf)?, 0);
+
+        assert_eq!(&storage[..8], b"ABCDEFGH");
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrap_count() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 6];
+        byte_stream.read(&mut buf)?;
+        assert_eq!(byte_stream.wrap_count(), 0);
+
+        byte_stream.write(b"ABCD")?;
+        assert_eq!(byte_stream.wrap_count(), 1);
+        byte_stream.read(&mut buf[..4])?;
+        assert_eq!(byte_stream.wrap_count(), 2);
+
+        byte_stream.write(b"12345678")?;
+        assert_eq!(byte_stream.wrap_count(), 3);
+        byte_stream.read(&mut buf[..6])?;
+        byte_stream.read(&mut buf[..2])?;
+        assert_eq!(byte_stream.wrap_count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrap_count_consume_and_splice() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"ABCD")?;
+        assert_eq!(byte_stream.wrap_count(), 1);
+        byte_stream.consume(5);
+        assert_eq!(byte_stream.wrap_count(), 2);
+
+        let mut src = ByteStream::new(8);
+        let mut dst = ByteStream::new(8);
+        src.write(b"123456")?;
+        src.read(&mut buf)?;
+        src.write(b"ABCD")?;
+        dst.write(b"12345")?;
+        dst.read(&mut buf)?;
+        assert_eq!(ByteStream::splice(&mut src, &mut dst, 100), 6);
+        assert_eq!(src.wrap_count(), 2);
+        assert_eq!(dst.wrap_count(), 1);
+
+        let mut result = String::new();
+        dst.read_to_string(&mut result)?;
+        assert_eq!(result, "556ABCD");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_exact_or_none() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 6];
+        byte_stream.read(&mut buf[..4])?;
+        byte_stream.write(b"ABCD")?;
+
+        let mut buf = [0; 6];
+        assert!(byte_stream.read_exact_or_none(&mut buf));
+        assert_eq!(&buf, b"56ABCD");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_exact_or_none_over_available() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345678")?;
+
+        let mut buf = [0; 3];
+        assert!(byte_stream.read_exact_or_none(&mut buf));
+        assert_eq!(&buf, b"123");
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "45678");
+        Ok(())
     }
 
-    pub fn capacity(&self) -> usize {
-        self.capacity
+    #[test]
+    fn test_read_exact_or_none_under_available() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345")?;
+
+        let mut buf = [b'x'; 6];
+        assert!(!byte_stream.read_exact_or_none(&mut buf));
+        assert_eq!(&buf, b"xxxxxx");
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "12345");
+        Ok(())
     }
-}
 
-impl Read for ByteStream {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let read_size = buf.len().min(self.used_capacity);
+    struct PartialSink {
+        data: Vec<u8>,
+        max_write: usize,
+        writes: usize,
+    }
 
-        let read_size_lo = read_size.min(self.capacity - self.head_index);
-        if read_size_lo > 0 {
-            buf[..read_size_lo]
-                .copy_from_slice(&self.buffer[self.head_index..self.head_index + read_size_lo]);
+    impl Write for PartialSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let size = buf.len().min(self.max_write);
+            self.data.extend_from_slice(&buf[..size]);
+            self.writes += 1;
+            Ok(size)
         }
 
-        if let Some(read_size_hi) = read_size.checked_sub(read_size_lo) {
-            if read_size_hi > 0 {
-                buf[read_size_lo..read_size].copy_from_slice(&self.buffer[..read_size_hi]);
-            }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
         }
+    }
 
-        self.used_capacity -= read_size;
-        self.head_index = (self.head_index + read_size) % self.capacity;
-        Ok(read_size)
+    #[test]
+    fn test_drain_to() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"ABCD")?;
+
+        let mut sink = PartialSink {
+            data: Vec::new(),
+            max_write: usize::MAX,
+            writes: 0,
+        };
+        assert_eq!(byte_stream.wrap_count(), 1);
+        assert_eq!(byte_stream.drain_to(&mut sink)?, 6);
+        assert_eq!(sink.data, b"56ABCD");
+        assert_eq!(sink.writes, 2);
+        assert_eq!(byte_stream.wrap_count(), 2);
+        assert_eq!(byte_stream.drain_to(&mut sink)?, 0);
+        assert_eq!(sink.writes, 2);
+        assert_eq!(byte_stream.wrap_count(), 2);
+        Ok(())
     }
-}
 
-impl Write for ByteStream {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let capacity_left = self.capacity - self.used_capacity;
-        let write_size = buf.len().min(capacity_left);
-        let tail_index = (self.head_index + self.used_capacity) % self.capacity;
+    #[test]
+    fn test_drain_to_partial_writes() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"ABCD")?;
 
-        let write_size_lo = write_size.min(self.capacity - tail_index);
-        if write_size_lo > 0 {
-            let buf_lo = &mut self.buffer[tail_index..tail_index + write_size_lo];
-            buf_lo.copy_from_slice(&buf[..write_size_lo]);
-        }
+        let mut sink = PartialSink {
+            data: Vec::new(),
+            max_write: 3,
+            writes: 0,
+        };
+        assert_eq!(byte_stream.drain_to(&mut sink)?, 3);
+        assert_eq!(sink.data, b"56A");
+        assert_eq!(byte_stream.drain_to(&mut sink)?, 3);
+        assert_eq!(sink.data, b"56ABCD");
 
-        if let Some(write_size_hi) = write_size.checked_sub(write_size_lo) {
-            if write_size_hi > 0 {
-                let buf_hi = &mut self.buffer[..write_size_hi];
-                buf_hi.copy_from_slice(&buf[write_size_lo..]);
-            }
-        }
+        byte_stream.write(b"12345")?;
+        assert_eq!(byte_stream.drain_to(&mut sink)?, 3);
+        assert_eq!(sink.writes, 4);
 
-        self.used_capacity += write_size;
-        Ok(write_size)
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "45");
+        Ok(())
     }
 
-    fn flush(&mut self) -> io::Result<()> {
+    #[test]
+    fn test_reader_view() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"ABCD")?;
+
+        let mut view = byte_stream.reader_view();
+        let mut buf = [0; 3];
+        view.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"56A");
+        let mut rest = Vec::new();
+        view.read_to_end(&mut rest)?;
+        assert_eq!(rest, b"BCD");
+
+        let mut full = String::new();
+        byte_stream.reader_view().read_to_string(&mut full)?;
+        assert_eq!(full, "56ABCD");
+
+        assert_eq!(byte_stream.bytes_written(), 10);
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "56ABCD");
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::io::{self, Read, Write};
+    fn hash_of(byte_stream: &ByteStream) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        byte_stream.hash(&mut hasher);
+        hasher.finish()
+    }
 
-    use super::ByteStream;
+    #[test]
+    fn test_eq_and_hash_ignore_layout() -> io::Result<()> {
+        let mut wrapped = ByteStream::new(8);
+        wrapped.write(b"123456")?;
+        let mut buf = [0; 5];
+        wrapped.read(&mut buf)?;
+        wrapped.write(b"ABCDE")?;
+
+        let mut contiguous = ByteStream::new(32);
+        contiguous.write(b"6ABCDE")?;
+
+        assert_eq!(wrapped.wrap_count(), 1);
+        assert!(wrapped == contiguous);
+        assert_eq!(hash_of(&wrapped), hash_of(&contiguous));
+
+        contiguous.write(b"F")?;
+        assert!(wrapped != contiguous);
+        Ok(())
+    }
 
     #[test]
-    fn test_simple() -> io::Result<()> {
-        let mut byte_stream = ByteStream::new(1024);
-        let input_string = b"abcdefgh".repeat(128);
+    fn test_eq_and_hash_large_contents() -> io::Result<()> {
+        let input: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let mut wrapped = ByteStream::new(1024);
+        wrapped.write(&[0; 700])?;
+        let mut buf = [0; 700];
+        wrapped.read(&mut buf)?;
+        wrapped.write(&input)?;
 
-        byte_stream.write(&input_string)?;
-        let mut read_buf = [0; 1024];
-        byte_stream.read(&mut read_buf)?;
-        assert_eq!(input_string, read_buf);
+        let mut contiguous = ByteStream::new(1000);
+        contiguous.write(&input)?;
 
+        assert!(wrapped == contiguous);
+        assert_eq!(hash_of(&wrapped), hash_of(&contiguous));
         Ok(())
     }
 
     #[test]
-    fn test_read_truncation() -> io::Result<()> {
-        let mut byte_stream = ByteStream::new(1024);
-        let input_string = b"abcdefgh".repeat(128);
+    fn test_with_initial_offset() -> io::Result<()> {
+        let mut byte_stream = ByteStream::with_initial_offset(8, 1000);
+        assert_eq!(byte_stream.bytes_read(), 1000);
+        assert_eq!(byte_stream.bytes_written(), 1000);
 
-        byte_stream.write(&input_string)?;
-        let mut read_buf = [0; 128];
-        byte_stream.read(&mut read_buf)?;
-        assert_eq!(input_string[0..128], read_buf);
+        byte_stream.set_write_quota(Some(6));
+        assert_eq!(byte_stream.write(b"12345678")?, 6);
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
+        assert_eq!(byte_stream.bytes_read(), 1004);
+        assert_eq!(byte_stream.bytes_written(), 1006);
 
+        // Nothing before the offset was ever buffered, so it cannot be sought back to
+        assert!(byte_stream.seek(SeekFrom::Start(999)).is_err());
+        assert_eq!(byte_stream.seek(SeekFrom::Start(1000))?, 1000);
+        assert_eq!(byte_stream.peek_u8(), Some(b'1'));
         Ok(())
     }
 
     #[test]
-    fn test_write_truncation() -> io::Result<()> {
-        let mut byte_stream = ByteStream::new(128);
-        let input_string = b"abcdefgh".repeat(128);
+    fn test_free_io_slices_across_wrap() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345")?;
+        let mut buf = [0; 4];
+        byte_stream.read(&mut buf)?;
 
-        byte_stream.write(&input_string)?;
-        let mut read_buf = [0; 128];
-        byte_stream.read(&mut read_buf)?;
-        assert_eq!(input_string[0..128], read_buf);
+        let mut source = io::Cursor::new(b"ABCDEFGHIJ".to_vec());
+        let mut slices = byte_stream.free_io_slices();
+        assert_eq!((slices[0].len(), slices[1].len()), (3, 4));
+        let read_size = source.read_vectored(&mut slices)?;
+        assert_eq!(read_size, 7);
+        byte_stream.commit(read_size);
+        assert_eq!(byte_stream.wrap_count(), 1);
+        assert_eq!(byte_stream.bytes_written(), 12);
+
+        let slices = byte_stream.free_io_slices();
+        assert_eq!((slices[0].len(), slices[1].len()), (0, 0));
 
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "5ABCDEFG");
         Ok(())
     }
 
     #[test]
-    fn test_write_read_multiple_times() -> io::Result<()> {
-        let mut byte_stream = ByteStream::new(16);
-        byte_stream.write(b"Hello")?;
-        byte_stream.write(b" ")?;
-        byte_stream.write(b"World")?;
+    #[should_panic(expected = "exceeds the free space")]
+    fn test_commit_past_free_space() {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.commit(9);
+    }
+
+    #[test]
+    fn test_consume_if_available() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 5];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"ABCD")?;
+
+        assert_eq!(byte_stream.consume_if_available(6), None);
+        assert_eq!(byte_stream.consume_if_available(4), Some(b"6ABC".to_vec()));
+        assert_eq!(byte_stream.consume_if_available(0), Some(Vec::new()));
+        assert_eq!(byte_stream.consume_if_available(2), None);
+        assert_eq!(byte_stream.consume_if_available(1), Some(b"D".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_consume_if_available_leaves_state_untouched() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123")?;
+        let position = byte_stream.save();
+
+        assert_eq!(byte_stream.consume_if_available(4), None);
+        assert_eq!(byte_stream.save(), position);
+        assert_eq!(byte_stream.peek_u8(), Some(b'1'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder() -> io::Result<()> {
+        let byte_stream = ByteStream::builder(8).build();
+        assert_eq!(byte_stream.capacity(), 8);
+        assert_eq!(byte_stream.overflow_policy(), OverflowPolicy::Truncate);
+        assert_eq!(byte_stream.remaining_quota(), None);
+        assert_eq!(byte_stream.bytes_read(), 0);
+
+        let mut byte_stream = ByteStream::builder(8)
+            .overflow_policy(OverflowPolicy::OverwriteOldest)
+            .write_quota(Some(10))
+            .initial_offset(100)
+            .build();
+        assert_eq!(byte_stream.write(b"12345678")?, 8);
+        assert_eq!(byte_stream.write(b"ABCD")?, 2);
+        assert_eq!(byte_stream.bytes_written(), 110);
+        assert_eq!(byte_stream.remaining_quota(), Some(0));
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "345678AB");
+        assert_eq!(byte_stream.bytes_read(), 110);
+        Ok(())
+    }
+
+    #[test]
+    fn test_overwrite_oldest_oversized_write_counts_skipped_bytes() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.set_overflow_policy(OverflowPolicy::OverwriteOldest);
+        byte_stream.write(b"123")?;
+        assert_eq!(byte_stream.write(b"ABCDEFGHIJ")?, 10);
+        assert_eq!(byte_stream.bytes_read(), 5);
+        assert_eq!(byte_stream.bytes_written(), 13);
+        assert_eq!(byte_stream.seek(SeekFrom::End(0))?, 13);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_all_vectored() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345")?;
+        let mut buf = [0; 5];
+        byte_stream.read(&mut buf)?;
+
+        let mut bufs = [
+            IoSlice::new(b""),
+            IoSlice::new(b"AB"),
+            IoSlice::new(b"CDE"),
+            IoSlice::new(b""),
+            IoSlice::new(b"FGH"),
+        ];
+        byte_stream.write_all_vectored(&mut bufs)?;
+        assert_eq!(byte_stream.wrap_count(), 1);
 
         let mut result = String::new();
         byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "ABCDEFGH");
+        Ok(())
+    }
 
-        assert_eq!(result, "Hello World");
+    #[test]
+    fn test_write_all_vectored_when_full() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12")?;
+
+        let mut bufs = [IoSlice::new(b"ABC"), IoSlice::new(b"DEFG")];
+        let err = byte_stream.write_all_vectored(&mut bufs).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "12ABCDEF");
         Ok(())
     }
 
     #[test]
-    fn test_circular_buffer_behavior() -> io::Result<()> {
+    fn test_read_until_delim() -> io::Result<()> {
         let mut byte_stream = ByteStream::new(8);
-        byte_stream.write(b"12345678")?;
+        byte_stream.write(b"1234567")?;
+        let mut buf = [0; 6];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"A\nB\nC")?;
+
+        let mut line = b"x".to_vec();
+        assert_eq!(byte_stream.read_until_delim(b'\n', &mut line), Some(3));
+        assert_eq!(line, b"x7A\n");
+        assert_eq!(byte_stream.wrap_count(), 2);
+
+        line.clear();
+        assert_eq!(byte_stream.read_until_delim(b'\n', &mut line), Some(2));
+        assert_eq!(line, b"B\n");
 
+        line.clear();
+        assert_eq!(byte_stream.read_until_delim(b'\n', &mut line), None);
+        assert!(line.is_empty());
+        assert_eq!(byte_stream.peek_u8(), Some(b'C'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unread() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
         let mut buf = [0; 4];
         byte_stream.read(&mut buf)?;
-        assert_eq!(&buf, b"1234");
 
-        byte_stream.write(b"ABCD")?;
+        assert_eq!(byte_stream.unread(b"34"), 2);
+        assert_eq!(byte_stream.bytes_read(), 2);
+        let mut result = [0; 4];
+        byte_stream.read_exact(&mut result)?;
+        assert_eq!(&result, b"3456");
+        Ok(())
+    }
+
+    #[test]
+    fn test_unread_across_wrap() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"12345678")?;
+        let mut buf = [0; 8];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"AB")?;
+        byte_stream.read(&mut buf[..1])?;
+
+        // Head is at index 1, so pushing back three bytes moves it past zero
+        assert_eq!(byte_stream.unread(b"XYZ"), 3);
+        assert_eq!(byte_stream.wrap_count(), 1);
+
+        // Only the free space is available for pushed back bytes
+        assert_eq!(byte_stream.unread(b"abcdef"), 4);
 
         let mut result = String::new();
         byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "cdefXYZB");
+        Ok(())
+    }
 
-        assert_eq!(result, "5678ABCD");
+    #[test]
+    fn test_unread_limited_to_bytes_read() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        assert_eq!(byte_stream.unread(b"12"), 0);
+
+        byte_stream.write(b"AB")?;
+        byte_stream.read(&mut [0; 1])?;
+        assert_eq!(byte_stream.unread(b"12"), 1);
+        assert_eq!(byte_stream.peek_u16_be(), Some(u16::from_be_bytes(*b"2B")));
         Ok(())
     }
 
     #[test]
-    fn test_partial_reads() -> io::Result<()> {
-        let mut byte_stream = ByteStream::new(16);
-        byte_stream.write(b"Hello World")?;
+    fn test_suggested_read_size() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        assert_eq!(byte_stream.suggested_read_size(64, 1024), 64);
+
+        byte_stream.write(b"1")?;
+        assert_eq!(byte_stream.suggested_read_size(64, 1024), 184);
+
+        byte_stream.write(b"234")?;
+        assert_eq!(byte_stream.suggested_read_size(64, 1024), 544);
+
+        byte_stream.write(b"5678")?;
+        assert_eq!(byte_stream.suggested_read_size(64, 1024), 1024);
+        assert_eq!(byte_stream.suggested_read_size(16, 16), 16);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds maximum")]
+    fn test_suggested_read_size_min_above_max() {
+        ByteStream::new(8).suggested_read_size(1024, 64);
+    }
+
+    #[test]
+    fn test_for_each_chunk() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        let mut chunks = Vec::new();
+        byte_stream.for_each_chunk(|chunk| chunks.push(chunk.to_vec()));
+        assert!(chunks.is_empty());
+
+        byte_stream.write(b"123456")?;
+        byte_stream.for_each_chunk(|chunk| chunks.push(chunk.to_vec()));
+        assert_eq!(chunks, [b"123456".to_vec()]);
 
         let mut buf = [0; 5];
         byte_stream.read(&mut buf)?;
-        assert_eq!(&buf, b"Hello");
+        byte_stream.write(b"ABCD")?;
+        chunks.clear();
+        byte_stream.for_each_chunk(|chunk| chunks.push(chunk.to_vec()));
+        assert_eq!(chunks, [b"6AB".to_vec(), b"CD".to_vec()]);
+        assert_eq!(byte_stream.bytes_read(), 5);
+        Ok(())
+    }
 
+    #[test]
+    fn test_read_buf() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(8);
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 5];
         byte_stream.read(&mut buf)?;
-        assert_eq!(&buf, b" Worl");
+        byte_stream.write(b"ABCD")?;
 
-        byte_stream.read(&mut buf)?;
-        assert_eq!(&buf[0..1], b"d");
+        // Pre-fill with a marker to check that nothing past the returned count is written
+        let mut storage = [MaybeUninit::new(b'-'); 8];
+        assert_eq!(byte_stream.read_buf(&mut storage[..4]), 4);
+        assert_eq!(byte_stream.wrap_count(), 2);
+        assert_eq!(byte_stream.read_buf(&mut storage[4..]), 1);
+        let storage = storage.map(|byte| unsafe { byte.assume_init() });
+        assert_eq!(&storage, b"6ABCD---");
 
+        let mut storage = [MaybeUninit::uninit(); 4];
+        assert_eq!(byte_stream.read_buf(&mut storage), 0);
         Ok(())
     }
 
+    #[derive(Clone, Default)]
+    struct SharedSink(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct FailingSink;
+
+    impl Write for FailingSink {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
     #[test]
-    fn test_capacity_and_overflow() -> io::Result<()> {
+    fn test_read_tap() -> io::Result<()> {
         let mut byte_stream = ByteStream::new(8);
-        assert_eq!(byte_stream.capacity(), 8);
+        let sink = SharedSink::default();
+        byte_stream.set_read_tap(Box::new(sink.clone()));
 
-        let write_result = byte_stream.write(b"123456789")?;
-        assert_eq!(write_result, 8); // Only 8 bytes should be written
+        byte_stream.write(b"123456")?;
+        let mut buf = [0; 5];
+        byte_stream.read(&mut buf)?;
+        byte_stream.write(b"ABCDE")?;
+        byte_stream.read(&mut buf[..4])?;
+        byte_stream.consume(1);
+        byte_stream.drain_to(&mut Vec::new())?;
+        assert_eq!(*sink.0.lock().unwrap(), b"123456ABCDE");
+        assert!(!byte_stream.read_tap_failed());
 
-        let mut result = String::new();
-        byte_stream.read_to_string(&mut result)?;
-        assert_eq!(result, "12345678");
+        byte_stream.remove_read_tap();
+        byte_stream.write(b"F")?;
+        byte_stream.read(&mut buf)?;
+        assert_eq!(sink.0.lock().unwrap().len(), 11);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_tap_splice_and_messages() -> io::Result<()> {
+        let mut src = ByteStream::new(8);
+        let mut dst = ByteStream::new(8);
+        let sink = SharedSink::default();
+        src.set_read_tap(Box::new(sink.clone()));
+
+        src.write(b"12345")?;
+        assert_eq!(ByteStream::splice(&mut src, &mut dst, 8), 5);
+        assert_eq!(*sink.0.lock().unwrap(), b"12345");
 
+        src.write(&2u32.to_be_bytes())?;
+        src.write(b"AB")?;
+        assert_eq!(src.messages().next(), Some(b"AB".to_vec()));
+        assert_eq!(*sink.0.lock().unwrap(), b"12345\0\0\0\x02AB");
         Ok(())
     }
 
+    #[cfg(feature = "futures")]
     #[test]
-    fn test_empty_reads() -> io::Result<()> {
+    fn test_read_tap_chunk_stream() -> io::Result<()> {
+        use futures::StreamExt;
+
         let mut byte_stream = ByteStream::new(8);
-        let mut buf = [0; 4];
-        let read_result = byte_stream.read(&mut buf)?;
-        assert_eq!(read_result, 0);
+        let sink = SharedSink::default();
+        byte_stream.set_read_tap(Box::new(sink.clone()));
+        byte_stream.write(b"1234")?;
+
+        let chunks: Vec<bytes::Bytes> =
+            futures::executor::block_on(byte_stream.into_chunk_stream().collect());
+        assert_eq!(chunks.concat(), b"1234");
+        assert_eq!(*sink.0.lock().unwrap(), b"1234");
         Ok(())
     }
 
     #[test]
-    fn test_full_writes() -> io::Result<()> {
+    fn test_auto_traits() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ByteStream>();
+    }
+
+    #[test]
+    fn test_read_tap_failure() -> io::Result<()> {
         let mut byte_stream = ByteStream::new(8);
-        byte_stream.write(b"12345678")?;
-        let write_result = byte_stream.write(b"9")?;
-        assert_eq!(write_result, 0); // No space left, should write 0 bytes
+        byte_stream.set_read_tap(Box::new(FailingSink));
+        byte_stream.write(b"123")?;
+
+        let mut buf = [0; 3];
+        assert_eq!(byte_stream.read(&mut buf)?, 3);
+        assert_eq!(&buf, b"123");
+        assert!(byte_stream.read_tap_failed());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_op_size() -> io::Result<()> {
+        let max_op_size = NonZeroUsize::new(3);
+        let mut byte_stream = ByteStream::builder(8).max_op_size(max_op_size).build();
+        assert_eq!(byte_stream.max_op_size(), max_op_size);
+
+        assert_eq!(byte_stream.write(b"1234567")?, 3);
+        assert_eq!(byte_stream.write(b"4567")?, 3);
+        assert_eq!(byte_stream.write(b"7")?, 1);
+
+        let mut buf = [0; 8];
+        assert_eq!(byte_stream.read(&mut buf)?, 3);
+        assert_eq!(&buf[..3], b"123");
+
+        byte_stream.write_all(b"ABCD")?;
+        let mut result = String::new();
+        byte_stream.read_to_string(&mut result)?;
+        assert_eq!(result, "4567ABCD");
+
+        byte_stream.set_max_op_size(None);
+        assert_eq!(byte_stream.write(b"12345678")?, 8);
         Ok(())
     }
 }
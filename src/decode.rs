@@ -0,0 +1,293 @@
+use std::io::{self, BufRead, Read};
+use std::ops::Range;
+
+use crate::byte_stream::ByteStream;
+use crate::error::FoamError;
+
+const DEFAULT_INPUT_CAPACITY: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Hex,
+    /// Standard alphabet with `=` padding
+    Base64,
+}
+
+impl Encoding {
+    fn unit_size(self) -> usize {
+        match self {
+            Encoding::Hex => 2,
+            Encoding::Base64 => 4,
+        }
+    }
+}
+
+/// Decodes hex or base64 text read from `source` on the fly.
+///
+/// Encoded input is staged in a `ByteStream` ring and ASCII whitespace is skipped. An encoding
+/// unit split across reads from the source is held until the rest of it arrives, and input that
+/// ends in the middle of a unit fails with `ErrorKind::InvalidData`. So does a unit with an
+/// invalid character, once the bytes decoded before it have been returned; the unit is then
+/// skipped so later reads continue after it.
+pub struct DecodeReader<R: Read> {
+    source: R,
+    ring: ByteStream,
+    encoding: Encoding,
+    unit: [u8; 4],
+    unit_size: usize,
+    decoded: [u8; 3],
+    decoded_range: Range<usize>,
+    pending_error: Option<FoamError>,
+}
+
+impl<R: Read> DecodeReader<R> {
+    pub fn new(source: R, encoding: Encoding) -> Self {
+        Self::with_capacity(source, encoding, DEFAULT_INPUT_CAPACITY)
+    }
+
+    /// Stages up to `capacity` encoded bytes at a time.
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_capacity(source: R, encoding: Encoding, capacity: usize) -> Self {
+        assert!(capacity > 0, "input capacity must be nonzero");
+        Self {
+            source,
+            ring: ByteStream::new(capacity),
+            encoding,
+            unit: [0; 4],
+            unit_size: 0,
+            decoded: [0; 3],
+            decoded_range: 0..0,
+            pending_error: None,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(err) = self.pending_error.take() {
+            return Err(err.into());
+        }
+
+        let mut read_size = 0;
+        while read_size < buf.len() {
+            if !self.decoded_range.is_empty() {
+                let size = self.decoded_range.len().min(buf.len() - read_size);
+                let start = self.decoded_range.start;
+                buf[read_size..read_size + size]
+                    .copy_from_slice(&self.decoded[start..start + size]);
+                self.decoded_range.start += size;
+                read_size += size;
+                continue;
+            }
+
+            let available = self.ring.fill_buf()?;
+            if available.is_empty() {
+                // Only go back to the source if nothing has been decoded yet, so a read never
+                // blocks while it has data to return
+                if read_size > 0 {
+                    break;
+                }
+                if self.ring.read_from(&mut self.source)? == 0 {
+                    if self.unit_size > 0 {
+                        return Err(FoamError::TruncatedEncoding.into());
+                    }
+                    break;
+                }
+                continue;
+            }
+
+            let mut consumed = 0;
+            let mut result = Ok(());
+            for &byte in available {
+                consumed += 1;
+                if byte.is_ascii_whitespace() {
+                    continue;
+                }
+                self.unit[self.unit_size] = byte;
+                self.unit_size += 1;
+                if self.unit_size == self.encoding.unit_size() {
+                    // An invalid unit is dropped whole, so a later read resumes after it
+                    self.unit_size = 0;
+                    result = match self.encoding {
+                        Encoding::Hex => decode_hex(&self.unit[..2], &mut self.decoded),
+                        Encoding::Base64 => decode_base64(&self.unit, &mut self.decoded),
+                    }
+                    .map(|size| self.decoded_range = 0..size);
+                    break;
+                }
+            }
+            self.ring.consume(consumed);
+            if let Err(err) = result {
+                // Hand out what was decoded before the invalid unit first
+                if read_size > 0 {
+                    self.pending_error = Some(err);
+                    break;
+                }
+                return Err(err.into());
+            }
+        }
+        Ok(read_size)
+    }
+}
+
+fn decode_hex(unit: &[u8], out: &mut [u8; 3]) -> Result<usize, FoamError> {
+    let nibble = |byte: u8| {
+        (byte as char)
+            .to_digit(16)
+            .map(|digit| digit as u8)
+            .ok_or(FoamError::InvalidEncodedCharacter(byte))
+    };
+    out[0] = nibble(unit[0])? << 4 | nibble(unit[1])?;
+    Ok(1)
+}
+
+fn decode_base64(unit: &[u8; 4], out: &mut [u8; 3]) -> Result<usize, FoamError> {
+    let sextet = |byte: u8| match byte {
+        b'A'..=b'Z' => Ok(byte - b'A'),
+        b'a'..=b'z' => Ok(byte - b'a' + 26),
+        b'0'..=b'9' => Ok(byte - b'0' + 52),
+        b'+' => Ok(62),
+        b'/' => Ok(63),
+        _ => Err(FoamError::InvalidEncodedCharacter(byte)),
+    };
+    let padding = match unit {
+        [_, _, b'=', b'='] => 2,
+        [_, _, _, b'='] => 1,
+        _ => 0,
+    };
+
+    let mut bits = 0u32;
+    for &byte in &unit[..4 - padding] {
+        bits = bits << 6 | sextet(byte)? as u32;
+    }
+    bits <<= 6 * padding;
+    out.copy_from_slice(&bits.to_be_bytes()[1..]);
+    Ok(3 - padding)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use super::{DecodeReader, Encoding};
+    use crate::error::FoamError;
+    use crate::test_util::ChunkedReader;
+
+    fn payload() -> Vec<u8> {
+        (0..1000u32).map(|i| (i * 7919 % 251) as u8).collect()
+    }
+
+    fn encode_base64(data: &[u8]) -> Vec<u8> {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut encoded = Vec::new();
+        for chunk in data.chunks(3) {
+            let mut bytes = [0; 3];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize]);
+                } else {
+                    encoded.push(b'=');
+                }
+            }
+        }
+        encoded
+    }
+
+    fn decode_bytewise(encoded: Vec<u8>, encoding: Encoding) -> io::Result<Vec<u8>> {
+        let mut reader = DecodeReader::with_capacity(ChunkedReader::new(encoded, 1), encoding, 8);
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn test_hex() -> io::Result<()> {
+        let encoded: String = payload().iter().map(|byte| format!("{byte:02X}")).collect();
+        assert_eq!(
+            decode_bytewise(encoded.into_bytes(), Encoding::Hex)?,
+            payload()
+        );
+        assert_eq!(
+            decode_bytewise(b"48656c6c6f\n2c20776f726c6421".to_vec(), Encoding::Hex)?,
+            b"Hello, world!"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_base64() -> io::Result<()> {
+        for len in [998, 999, 1000] {
+            let data = &payload()[..len];
+            assert_eq!(
+                decode_bytewise(encode_base64(data), Encoding::Base64)?,
+                data
+            );
+        }
+        assert_eq!(
+            decode_bytewise(b"SGVsbG8s\r\nIHdvcmxkIQ==".to_vec(), Encoding::Base64)?,
+            b"Hello, world!"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let cause = |err: io::Error| err.get_ref().and_then(|err| err.downcast_ref()).copied();
+
+        let err = decode_bytewise(b"4g".to_vec(), Encoding::Hex).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(cause(err), Some(FoamError::InvalidEncodedCharacter(b'g')));
+
+        let err = decode_bytewise(b"SGVsbG8".to_vec(), Encoding::Base64).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(cause(err), Some(FoamError::TruncatedEncoding));
+    }
+
+    #[test]
+    fn test_invalid_input_after_valid_units() -> io::Result<()> {
+        let mut reader = DecodeReader::new(&b"41424g43"[..], Encoding::Hex);
+        let mut buf = [0; 8];
+        assert_eq!(reader.read(&mut buf)?, 2);
+        assert_eq!(&buf[..2], b"AB");
+
+        let err = reader.read(&mut buf).unwrap_err();
+        let cause = err.get_ref().and_then(|err| err.downcast_ref()).copied();
+        assert_eq!(cause, Some(FoamError::InvalidEncodedCharacter(b'g')));
+
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output)?;
+        assert_eq!(output, b"C");
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "input capacity must be nonzero")]
+    fn test_zero_capacity() {
+        DecodeReader::with_capacity(&b"414243"[..], Encoding::Hex, 0);
+    }
+
+    #[test]
+    fn test_read_after_invalid_input() -> io::Result<()> {
+        for (encoded, encoding, expected) in [
+            (&b"4g41"[..], Encoding::Hex, &b"A"[..]),
+            (b"SG!sSGk=", Encoding::Base64, b"Hi"),
+        ] {
+            let mut reader = DecodeReader::with_capacity(encoded, encoding, 8);
+            let mut buf = [0; 8];
+            let err = reader.read(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            let mut output = Vec::new();
+            reader.read_to_end(&mut output)?;
+            assert_eq!(output, expected);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::{self, BufRead, Read};
+
+use flate2::bufread::{DeflateDecoder, GzDecoder, ZlibDecoder};
+
+use crate::byte_stream::ByteStream;
+
+const DEFAULT_INPUT_CAPACITY: usize = 8192;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Deflate,
+    Gzip,
+    Zlib,
+}
+
+/// Decompresses data read from `source` on the fly.
+///
+/// Compressed input is staged in a `ByteStream` ring, so a compressed block may span any number of
+/// reads from the source; the decoder only sees more input once the ring has been refilled.
+pub struct DecompressReader<R: Read> {
+    decoder: Decoder<R>,
+}
+
+enum Decoder<R: Read> {
+    Deflate(DeflateDecoder<RingInput<R>>),
+    Gzip(GzDecoder<RingInput<R>>),
+    Zlib(ZlibDecoder<RingInput<R>>),
+}
+
+impl<R: Read> DecompressReader<R> {
+    pub fn new(source: R, format: Format) -> Self {
+        Self::with_capacity(source, format, DEFAULT_INPUT_CAPACITY)
+    }
+
+    /// Stages up to `capacity` compressed bytes at a time.
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_capacity(source: R, format: Format, capacity: usize) -> Self {
+        assert!(capacity > 0, "input capacity must be nonzero");
+        let input = RingInput {
+            source,
+            ring: ByteStream::new(capacity),
+        };
+        let decoder = match format {
+            Format::Deflate => Decoder::Deflate(DeflateDecoder::new(input)),
+            Format::Gzip => Decoder::Gzip(GzDecoder::new(input)),
+            Format::Zlib => Decoder::Zlib(ZlibDecoder::new(input)),
+        };
+        Self { decoder }
+    }
+
+    pub fn into_inner(self) -> R {
+        match self.decoder {
+            Decoder::Deflate(decoder) => decoder.into_inner().source,
+            Decoder::Gzip(decoder) => decoder.into_inner().source,
+            Decoder::Zlib(decoder) => decoder.into_inner().source,
+        }
+    }
+}
+
+impl<R: Read> Read for DecompressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.decoder {
+            Decoder::Deflate(decoder) => decoder.read(buf),
+            Decoder::Gzip(decoder) => decoder.read(buf),
+            Decoder::Zlib(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+struct RingInput<R> {
+    source: R,
+    ring: ByteStream,
+}
+
+impl<R: Read> Read for RingInput<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read_size = available.len().min(buf.len());
+        buf[..read_size].copy_from_slice(&available[..read_size]);
+        self.consume(read_size);
+        Ok(read_size)
+    }
+}
+
+impl<R: Read> BufRead for RingInput<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.ring.fill_buf()?.is_empty() {
+            self.ring.read_from(&mut self.source)?;
+        }
+        self.ring.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.ring.consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+
+    use super::{DecompressReader, Format};
+    use crate::test_util::ChunkedReader;
+
+    fn payload() -> Vec<u8> {
+        (0..4096u32)
+            .flat_map(|i| format!("line {i}: {}\n", i * 7919 % 1013).into_bytes())
+            .collect()
+    }
+
+    fn decompress_in_chunks(compressed: Vec<u8>, format: Format) -> io::Result<Vec<u8>> {
+        let mut reader =
+            DecompressReader::with_capacity(ChunkedReader::new(compressed, 7), format, 16);
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn test_gzip() -> io::Result<()> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&payload())?;
+        let compressed = encoder.finish()?;
+
+        assert_eq!(decompress_in_chunks(compressed, Format::Gzip)?, payload());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deflate() -> io::Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&payload())?;
+        let compressed = encoder.finish()?;
+
+        assert_eq!(
+            decompress_in_chunks(compressed, Format::Deflate)?,
+            payload()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zlib() -> io::Result<()> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&payload())?;
+        let compressed = encoder.finish()?;
+
+        assert_eq!(decompress_in_chunks(compressed, Format::Zlib)?, payload());
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "input capacity must be nonzero")]
+    fn test_zero_capacity() {
+        DecompressReader::with_capacity(&b""[..], Format::Gzip, 0);
+    }
+
+    #[test]
+    fn test_truncated_input() -> io::Result<()> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&payload())?;
+        let mut compressed = encoder.finish()?;
+        compressed.truncate(compressed.len() / 2);
+
+        assert!(decompress_in_chunks(compressed, Format::Gzip).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Failure causes reported by this crate.
+///
+/// APIs bound to `std::io` traits still return `io::Error`; those errors wrap a `FoamError`
+/// that can be recovered with `io::Error::get_ref` and `downcast_ref`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FoamError {
+    /// Data read back does not match what was written
+    Inconsistency,
+    /// A saved position is ahead of the stream it is restored into
+    SavedPositionAhead,
+    /// Data a saved position or seek refers to has been reused by later writes
+    DataOverwritten,
+    /// A seek target lies outside the retained data
+    SeekOutOfRange,
+    /// A seek target is negative or overflows
+    InvalidSeek,
+    /// Encoded input contains a character outside its alphabet
+    InvalidEncodedCharacter(u8),
+    /// Encoded input ends in the middle of an encoding unit
+    TruncatedEncoding,
+}
+
+impl fmt::Display for FoamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FoamError::Inconsistency => "inconsistency detected in read data",
+            FoamError::SavedPositionAhead => "saved position is ahead of the stream",
+            FoamError::DataOverwritten => "saved data has been overwritten",
+            FoamError::SeekOutOfRange => "seek position is outside the retained data",
+            FoamError::InvalidSeek => "invalid seek to a negative or overflowing position",
+            FoamError::InvalidEncodedCharacter(byte) => {
+                return write!(f, "invalid character {:?} in encoded input", *byte as char);
+            }
+            FoamError::TruncatedEncoding => "input ends in the middle of an encoding unit",
+        })
+    }
+}
+
+impl Error for FoamError {}
+
+impl From<FoamError> for io::Error {
+    fn from(err: FoamError) -> Self {
+        let kind = match err {
+            FoamError::Inconsistency
+            | FoamError::InvalidEncodedCharacter(_)
+            | FoamError::TruncatedEncoding => io::ErrorKind::InvalidData,
+            FoamError::SavedPositionAhead
+            | FoamError::DataOverwritten
+            | FoamError::SeekOutOfRange
+            | FoamError::InvalidSeek => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::FoamError;
+
+    #[test]
+    fn test_into_io_error() {
+        let err = io::Error::from(FoamError::Inconsistency);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(&FoamError::Inconsistency)
+        );
+
+        let err = io::Error::from(FoamError::DataOverwritten);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "saved data has been overwritten");
+    }
+}
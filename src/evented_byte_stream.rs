@@ -0,0 +1,202 @@
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+use crate::byte_stream::ByteStream;
+
+/// A `ByteStream` paired with two eventfds that track its readiness, so it can be registered in
+/// epoll alongside real sockets.
+///
+/// The readable fd is readable while data is buffered, and the writable fd is readable while
+/// there is free space. Both are level-triggered and kept in sync after every read and write.
+///
+/// If updating the eventfds fails after data has moved, the read or write still reports the
+/// bytes it moved and the failure is kept for `take_readiness_error`. The next read or write
+/// retries the update first and fails without moving data if it fails again.
+pub struct EventedByteStream {
+    inner: ByteStream,
+    readable: EventFd,
+    writable: EventFd,
+    readiness_error: Option<io::Error>,
+}
+
+impl EventedByteStream {
+    pub fn new(capacity: usize) -> io::Result<Self> {
+        Self::from_byte_stream(ByteStream::new(capacity))
+    }
+
+    pub fn from_byte_stream(inner: ByteStream) -> io::Result<Self> {
+        let mut evented = Self {
+            inner,
+            readable: EventFd::new()?,
+            writable: EventFd::new()?,
+            readiness_error: None,
+        };
+        evented.sync_readiness()?;
+        Ok(evented)
+    }
+
+    pub fn readable_fd(&self) -> RawFd {
+        self.readable.fd.as_raw_fd()
+    }
+
+    pub fn writable_fd(&self) -> RawFd {
+        self.writable.fd.as_raw_fd()
+    }
+
+    pub fn get_ref(&self) -> &ByteStream {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> ByteStream {
+        self.inner
+    }
+
+    /// Takes the error from the last eventfd update that failed after a read or write.
+    pub fn take_readiness_error(&mut self) -> Option<io::Error> {
+        self.readiness_error.take()
+    }
+
+    /// Updates the eventfds after data has moved, keeping any failure instead of returning it so
+    /// the caller still learns how many bytes moved.
+    fn sync_readiness_after_transfer(&mut self) {
+        if let Err(err) = self.sync_readiness() {
+            self.readiness_error = Some(err);
+        }
+    }
+
+    fn sync_readiness(&mut self) -> io::Result<()> {
+        let (readable, writable) = self.inner.readiness();
+        self.readable.set(readable)?;
+        self.writable.set(writable)
+    }
+}
+
+impl Read for EventedByteStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.sync_readiness()?;
+        let read_size = self.inner.read(buf)?;
+        self.sync_readiness_after_transfer();
+        Ok(read_size)
+    }
+}
+
+impl Write for EventedByteStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sync_readiness()?;
+        let write_size = self.inner.write(buf)?;
+        self.sync_readiness_after_transfer();
+        Ok(write_size)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct EventFd {
+    fd: OwnedFd,
+    signaled: bool,
+}
+
+impl EventFd {
+    fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            signaled: false,
+        })
+    }
+
+    fn set(&mut self, signaled: bool) -> io::Result<()> {
+        if self.signaled == signaled {
+            return Ok(());
+        }
+
+        let mut value: u64 = 1;
+        let result = unsafe {
+            let value = &mut value as *mut u64 as *mut libc::c_void;
+            if signaled {
+                libc::write(self.fd.as_raw_fd(), value, 8)
+            } else {
+                // Reading resets the counter to zero
+                libc::read(self.fd.as_raw_fd(), value, 8)
+            }
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.signaled = signaled;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+    use std::os::fd::RawFd;
+
+    use super::EventedByteStream;
+
+    fn poll_readable(epoll_fd: RawFd) -> io::Result<bool> {
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; 1];
+        let ready = unsafe { libc::epoll_wait(epoll_fd, events.as_mut_ptr(), 1, 0) };
+        if ready < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ready == 1)
+    }
+
+    fn register(fd: RawFd) -> io::Result<RawFd> {
+        let epoll_fd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epoll_fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut event = libc::epoll_event {
+            events: libc::EPOLLIN as u32,
+            u64: fd as u64,
+        };
+        if unsafe { libc::epoll_ctl(epoll_fd, libc::EPOLL_CTL_ADD, fd, &mut event) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(epoll_fd)
+    }
+
+    #[test]
+    fn test_readable_fd() -> io::Result<()> {
+        let mut byte_stream = EventedByteStream::new(8)?;
+        let epoll_fd = register(byte_stream.readable_fd())?;
+        assert!(!poll_readable(epoll_fd)?);
+
+        byte_stream.write_all(b"1234")?;
+        assert!(poll_readable(epoll_fd)?);
+
+        let mut buf = [0; 2];
+        byte_stream.read_exact(&mut buf)?;
+        assert!(poll_readable(epoll_fd)?);
+        byte_stream.read_exact(&mut buf)?;
+        assert!(!poll_readable(epoll_fd)?);
+
+        unsafe { libc::close(epoll_fd) };
+        Ok(())
+    }
+
+    #[test]
+    fn test_writable_fd() -> io::Result<()> {
+        let mut byte_stream = EventedByteStream::new(8)?;
+        let epoll_fd = register(byte_stream.writable_fd())?;
+        assert!(poll_readable(epoll_fd)?);
+
+        byte_stream.write_all(b"12345678")?;
+        assert!(!poll_readable(epoll_fd)?);
+
+        let mut buf = [0; 1];
+        byte_stream.read_exact(&mut buf)?;
+        assert!(poll_readable(epoll_fd)?);
+
+        unsafe { libc::close(epoll_fd) };
+        Ok(())
+    }
+}
@@ -1 +1,12 @@
 pub mod byte_stream;
+pub mod decode;
+#[cfg(feature = "flate")]
+pub mod decompress;
+pub mod error;
+#[cfg(feature = "linux")]
+pub mod evented_byte_stream;
+pub mod rate_limit;
+pub mod record_queue;
+pub mod ring_log;
+#[cfg(test)]
+mod test_util;
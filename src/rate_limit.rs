@@ -0,0 +1,137 @@
+use std::io::{self, Read};
+use std::time::Instant;
+
+/// Token-bucket rate limiter over any reader, typically a `ByteStream`.
+///
+/// The bucket starts full on the first read and refills at `bytes_per_second` up to `burst`
+/// bytes. A read with an empty bucket fails with `ErrorKind::WouldBlock`.
+pub struct RateLimitedReader<R: Read> {
+    inner: R,
+    bytes_per_second: f64,
+    burst: f64,
+    tokens: f64,
+    last_refill: Option<Instant>,
+}
+
+impl<R: Read> RateLimitedReader<R> {
+    pub fn new(inner: R, bytes_per_second: u64, burst: u64) -> Self {
+        Self {
+            inner,
+            bytes_per_second: bytes_per_second as f64,
+            burst: burst as f64,
+            tokens: burst as f64,
+            last_refill: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Same as `read`, but refills the bucket as of `now` instead of the current time.
+    pub fn read_at(&mut self, buf: &mut [u8], now: Instant) -> io::Result<usize> {
+        self.refill(now);
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let allowed = self.tokens as usize;
+        if allowed == 0 {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        let read_size = buf.len().min(allowed);
+        let read_size = self.inner.read(&mut buf[..read_size])?;
+        self.tokens -= read_size as f64;
+        Ok(read_size)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(last_refill) = self.last_refill {
+            let elapsed = now.saturating_duration_since(last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.bytes_per_second).min(self.burst);
+        }
+        self.last_refill = Some(now);
+    }
+}
+
+impl<R: Read> Read for RateLimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_at(buf, Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::time::{Duration, Instant};
+
+    use super::RateLimitedReader;
+    use crate::byte_stream::ByteStream;
+
+    #[test]
+    fn test_burst_then_would_block() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(1024);
+        byte_stream.write_all(&[b'x'; 1024])?;
+        let mut reader = RateLimitedReader::new(byte_stream, 100, 50);
+        let now = Instant::now();
+
+        let mut buf = [0; 1024];
+        assert_eq!(reader.read_at(&mut buf, now)?, 50);
+        let err = reader.read_at(&mut buf, now).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        assert_eq!(
+            reader.read_at(&mut buf, now + Duration::from_millis(100))?,
+            10
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rate_over_interval() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(4096);
+        byte_stream.write_all(&[b'x'; 4096])?;
+        let mut reader = RateLimitedReader::new(byte_stream, 1000, 100);
+        let start = Instant::now();
+
+        let mut total = 0;
+        let mut buf = [0; 64];
+        for tick in 0..=100 {
+            let now = start + Duration::from_millis(tick * 10);
+            loop {
+                match reader.read_at(&mut buf, now) {
+                    Ok(read_size) => total += read_size,
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+
+        // One second at 1000 B/s on top of the initial 100 byte burst
+        assert!(total <= 1100, "delivered {total} bytes");
+        assert!(total >= 1090, "delivered {total} bytes");
+        Ok(())
+    }
+
+    #[test]
+    fn test_burst_caps_idle_refill() -> io::Result<()> {
+        let mut byte_stream = ByteStream::new(1024);
+        byte_stream.write_all(&[b'x'; 1024])?;
+        let mut reader = RateLimitedReader::new(byte_stream, 100, 50);
+        let now = Instant::now();
+
+        let mut buf = [0; 1024];
+        assert_eq!(reader.read_at(&mut buf, now)?, 50);
+        assert_eq!(reader.read_at(&mut buf, now + Duration::from_secs(60))?, 50);
+        Ok(())
+    }
+}
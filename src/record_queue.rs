@@ -0,0 +1,180 @@
+use std::io::Write;
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use crate::byte_stream::ByteStream;
+
+/// Bounded multi-producer multi-consumer queue of `N`-byte records over a `ByteStream`.
+///
+/// All operations take one mutex. Records are popped in the order they were pushed, but threads
+/// blocked in `push` or `pop` are not woken in any particular order, so a waiting thread can be
+/// overtaken by others.
+pub struct RecordQueue<const N: usize> {
+    capacity: usize,
+    ring: Mutex<ByteStream>,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+impl<const N: usize> RecordQueue<N> {
+    /// Creates a queue holding up to `capacity` records. `N` must be nonzero, which is checked
+    /// at compile time.
+    pub fn new(capacity: usize) -> Self {
+        const { assert!(N > 0, "records must be at least one byte") };
+        Self {
+            capacity,
+            ring: Mutex::new(ByteStream::new(capacity * N)),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Pushes `record`, or hands it back if the queue is full.
+    pub fn try_push(&self, record: [u8; N]) -> Result<(), [u8; N]> {
+        let mut ring = self.lock();
+        if !Self::has_room(&ring) {
+            return Err(record);
+        }
+        Self::push_locked(&mut ring, &record);
+        self.not_empty.notify_one();
+        Ok(())
+    }
+
+    pub fn try_pop(&self) -> Option<[u8; N]> {
+        let mut ring = self.lock();
+        let record = Self::pop_locked(&mut ring)?;
+        self.not_full.notify_one();
+        Some(record)
+    }
+
+    /// Pushes `record`, waiting for room if the queue is full.
+    pub fn push(&self, record: [u8; N]) {
+        let mut ring = self
+            .not_full
+            .wait_while(self.lock(), |ring| !Self::has_room(ring))
+            .unwrap();
+        Self::push_locked(&mut ring, &record);
+        self.not_empty.notify_one();
+    }
+
+    /// Pops the oldest record, waiting for one if the queue is empty.
+    pub fn pop(&self) -> [u8; N] {
+        let mut ring = self
+            .not_empty
+            .wait_while(self.lock(), |ring| Self::len_bytes(ring) < N)
+            .unwrap();
+        let record = Self::pop_locked(&mut ring).expect("a record is buffered");
+        self.not_full.notify_one();
+        record
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ByteStream> {
+        self.ring.lock().unwrap()
+    }
+
+    fn len_bytes(ring: &ByteStream) -> usize {
+        (ring.bytes_written() - ring.bytes_read()) as usize
+    }
+
+    fn has_room(ring: &ByteStream) -> bool {
+        ring.capacity() - Self::len_bytes(ring) >= N
+    }
+
+    fn push_locked(ring: &mut ByteStream, record: &[u8; N]) {
+        ring.write_all(record).expect("room was checked");
+    }
+
+    fn pop_locked(ring: &mut ByteStream) -> Option<[u8; N]> {
+        let mut record = [0; N];
+        ring.read_exact_or_none(&mut record).then_some(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::RecordQueue;
+
+    #[test]
+    fn test_try_push_and_pop() {
+        let queue = RecordQueue::<3>::new(2);
+        assert_eq!(queue.capacity(), 2);
+        assert_eq!(queue.try_pop(), None);
+
+        assert_eq!(queue.try_push(*b"abc"), Ok(()));
+        assert_eq!(queue.try_push(*b"def"), Ok(()));
+        assert_eq!(queue.try_push(*b"ghi"), Err(*b"ghi"));
+
+        assert_eq!(queue.try_pop(), Some(*b"abc"));
+        assert_eq!(queue.try_push(*b"ghi"), Ok(()));
+        assert_eq!(queue.try_pop(), Some(*b"def"));
+        assert_eq!(queue.try_pop(), Some(*b"ghi"));
+        assert_eq!(queue.try_pop(), None);
+    }
+
+    #[test]
+    fn test_stress() {
+        const PRODUCERS: u32 = 4;
+        const CONSUMERS: u32 = 4;
+        const RECORDS_PER_PRODUCER: u32 = 2000;
+        let queue = Arc::new(RecordQueue::<8>::new(16));
+
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|producer| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || {
+                    for seq in 0..RECORDS_PER_PRODUCER {
+                        let mut record = [0; 8];
+                        record[..4].copy_from_slice(&producer.to_be_bytes());
+                        record[4..].copy_from_slice(&seq.to_be_bytes());
+                        if seq % 2 == 0 {
+                            queue.push(record);
+                        } else {
+                            while let Err(rejected) = queue.try_push(record) {
+                                record = rejected;
+                                thread::yield_now();
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || {
+                    (0..PRODUCERS * RECORDS_PER_PRODUCER / CONSUMERS)
+                        .map(|_| queue.pop())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        let mut records: Vec<_> = consumers
+            .into_iter()
+            .flat_map(|consumer| consumer.join().unwrap())
+            .collect();
+        records.sort();
+
+        let expected: Vec<_> = (0..PRODUCERS)
+            .flat_map(|producer| {
+                (0..RECORDS_PER_PRODUCER).map(move |seq| {
+                    let mut record = [0; 8];
+                    record[..4].copy_from_slice(&producer.to_be_bytes());
+                    record[4..].copy_from_slice(&seq.to_be_bytes());
+                    record
+                })
+            })
+            .collect();
+        assert_eq!(records, expected);
+        assert_eq!(queue.try_pop(), None);
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+use crate::byte_stream::{ByteStream, OverflowPolicy};
+
+const HEADER_SIZE: usize = 12;
+
+/// Bounded log of recent timestamped messages over an overwrite-oldest `ByteStream`.
+///
+/// Each entry is a big-endian `u64` of nanoseconds since the log was created, a big-endian `u32`
+/// message length, and the message. Entries whose start has been overwritten are dropped.
+pub struct RingLog {
+    inner: ByteStream,
+    epoch: Instant,
+    entry_offsets: VecDeque<u64>,
+}
+
+impl RingLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: ByteStream::builder(capacity)
+                .overflow_policy(OverflowPolicy::OverwriteOldest)
+                .build(),
+            epoch: Instant::now(),
+            entry_offsets: VecDeque::new(),
+        }
+    }
+
+    pub fn push_entry(&mut self, msg: &str) {
+        self.push_entry_at(msg, Instant::now());
+    }
+
+    /// Same as `push_entry`, but timestamps the entry with `at` instead of the current time.
+    pub fn push_entry_at(&mut self, msg: &str, at: Instant) {
+        let nanos = at.saturating_duration_since(self.epoch).as_nanos() as u64;
+        let mut entry = Vec::with_capacity(HEADER_SIZE + msg.len());
+        entry.extend_from_slice(&nanos.to_be_bytes());
+        entry.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+        entry.extend_from_slice(msg.as_bytes());
+
+        self.entry_offsets.push_back(self.inner.bytes_written());
+        self.inner
+            .write_all(&entry)
+            .expect("overwrite-oldest writes always succeed");
+
+        // Skip the remains of any entry whose start was overwritten, so the stream always begins
+        // at an entry boundary
+        let bytes_read = self.inner.bytes_read();
+        while self
+            .entry_offsets
+            .front()
+            .is_some_and(|&offset| offset < bytes_read)
+        {
+            self.entry_offsets.pop_front();
+        }
+        let first_entry = self
+            .entry_offsets
+            .front()
+            .copied()
+            .unwrap_or(self.inner.bytes_written());
+        self.inner.consume((first_entry - bytes_read) as usize);
+    }
+
+    /// Complete entries in the order they were pushed.
+    pub fn iter_entries(&mut self) -> impl Iterator<Item = (Instant, &str)> {
+        let epoch = self.epoch;
+        let mut data = self.inner.make_contiguous();
+        std::iter::from_fn(move || {
+            let (header, rest) = data.split_first_chunk::<HEADER_SIZE>()?;
+            let (nanos, len) = header.split_at(8);
+            let nanos = u64::from_be_bytes(nanos.try_into().unwrap());
+            let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+            let (msg, rest) = rest.split_at(len);
+            data = rest;
+            let msg = std::str::from_utf8(msg).expect("entries are written from `&str`");
+            Some((epoch + Duration::from_nanos(nanos), msg))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::RingLog;
+
+    #[test]
+    fn test_iter_entries() {
+        let mut ring_log = RingLog::new(64);
+        let start = Instant::now();
+        ring_log.push_entry_at("first", start);
+        ring_log.push_entry_at("second", start + Duration::from_millis(5));
+
+        let entries: Vec<_> = ring_log.iter_entries().collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].1, "first");
+        assert_eq!(entries[1].1, "second");
+        assert_eq!(entries[1].0 - entries[0].0, Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_overflow_keeps_recent_complete_entries() {
+        // Each entry is 12 header bytes plus a 6-byte message
+        let mut ring_log = RingLog::new(40);
+        for i in 0..5 {
+            ring_log.push_entry(&format!("entry{i}"));
+        }
+
+        let messages: Vec<_> = ring_log.iter_entries().map(|(_, msg)| msg).collect();
+        assert_eq!(messages, ["entry3", "entry4"]);
+
+        ring_log.push_entry(&"x".repeat(64));
+        assert_eq!(ring_log.iter_entries().count(), 0);
+
+        ring_log.push_entry("after");
+        let messages: Vec<_> = ring_log.iter_entries().map(|(_, msg)| msg).collect();
+        assert_eq!(messages, ["after"]);
+    }
+}
@@ -0,0 +1,30 @@
+use std::io::{self, Read};
+
+/// Reader that hands out at most `chunk_size` bytes per `read`.
+pub(crate) struct ChunkedReader {
+    data: Vec<u8>,
+    position: usize,
+    chunk_size: usize,
+}
+
+impl ChunkedReader {
+    pub(crate) fn new(data: Vec<u8>, chunk_size: usize) -> Self {
+        Self {
+            data,
+            position: 0,
+            chunk_size,
+        }
+    }
+}
+
+impl Read for ChunkedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = buf
+            .len()
+            .min(self.chunk_size)
+            .min(self.data.len() - self.position);
+        buf[..size].copy_from_slice(&self.data[self.position..self.position + size]);
+        self.position += size;
+        Ok(size)
+    }
+}